@@ -0,0 +1,9 @@
+#![no_main]
+
+use jmespath_native::JMESSlice;
+use libfuzzer_sys::fuzz_target;
+
+// Parsing should only ever fail with an error, never panic
+fuzz_target!(|s: &str| {
+    let _ = s.parse::<JMESSlice>();
+});
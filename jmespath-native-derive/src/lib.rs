@@ -0,0 +1,72 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{ext::IdentExt as _, parse_macro_input, parse_quote, Data, DeriveInput, Fields};
+
+/// Generate `fn from_value(value: Value) -> Option<Self>` for a struct with named fields,
+/// which takes each field from an object by name and deserializes it.
+///
+/// Returns `None` if `value` isn't an object, or if any field fails to deserialize,
+/// including if it is missing and the field isn't an `Option`.
+#[proc_macro_derive(JMESPath)]
+pub fn derive_jmespath(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input,
+                    "JMESPath can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input,
+                "JMESPath can only be derived for structs",
+            ))
+        }
+    };
+    let idents = fields
+        .iter()
+        .map(|field| field.ident.as_ref().expect("Fields are named"));
+    let keys = idents.clone().map(|ident| ident.unraw().to_string());
+    let name = &input.ident;
+    // Bound each field, rather than each type parameter, so generic fields like `Vec<T>` work
+    let mut generics = input.generics.clone();
+    let predicates = &mut generics.make_where_clause().predicates;
+    for field in fields {
+        let ty = &field.ty;
+        predicates.push(parse_quote! {
+            #ty: ::jmespath_native::__private::serde::de::DeserializeOwned
+        });
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            pub fn from_value(
+                value: ::jmespath_native::__private::serde_json::Value,
+            ) -> ::core::option::Option<Self> {
+                let ::jmespath_native::__private::serde_json::Value::Object(mut map) = value else {
+                    return ::core::option::Option::None;
+                };
+                ::core::option::Option::Some(Self {
+                    #(
+                        #idents: ::jmespath_native::__private::serde_json::from_value(
+                            map.remove(#keys)
+                                .unwrap_or(::jmespath_native::__private::serde_json::Value::Null),
+                        )
+                        .ok()?,
+                    )*
+                })
+            }
+        }
+    })
+}
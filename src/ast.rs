@@ -0,0 +1,1190 @@
+//! Syntax tree for JMESPath expressions.
+
+use crate::JMESSlice;
+use serde_json::Value;
+use std::{fmt, iter::Peekable, mem, num::NonZeroIsize, str, sync::Mutex, vec};
+use thiserror::Error;
+
+/// A parsed JMESPath expression.
+///
+/// [`fmt::Display`] prints the canonical form, which parses back to an equivalent expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expression {
+    /// `@`
+    Current,
+    /// `foo`, or `"foo bar"` for keys which aren't valid unquoted
+    Identifier(String),
+    /// `[0]`
+    Index(isize),
+    /// `lhs.rhs`, or `lhs[0]`
+    Subexpression(Box<Expression>, Box<Expression>),
+    /// `$name`
+    Variable(String),
+    /// `` `json` ``, or `'raw string'`
+    Literal(Value),
+    /// `lhs < rhs`
+    Comparison(Comparator, Box<Expression>, Box<Expression>),
+    /// `!expression`, which is `true` if `expression` is falsey
+    Not(Box<Expression>),
+    /// `lhs || rhs`, which is `lhs` if it is truthy, else `rhs`
+    Or(Box<Expression>, Box<Expression>),
+    /// `lhs && rhs`, which is `lhs` if it is falsey, else `rhs`
+    And(Box<Expression>, Box<Expression>),
+    /// `lhs | rhs`, where `rhs` is evaluated against the result of `lhs`, which stops any projection
+    Pipe(Box<Expression>, Box<Expression>),
+    /// `lhs[?condition].rhs`, where `rhs` is projected over the elements of `lhs` for which `condition` is truthy
+    FilterProjection {
+        lhs: Box<Expression>,
+        condition: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    /// `lhs[start:end:step].rhs`, where `rhs` is projected over the slice of `lhs`
+    SliceProjection {
+        lhs: Box<Expression>,
+        slice: JMESSlice,
+        rhs: Box<Expression>,
+        cache: SliceCache,
+    },
+    /// `lhs.*.rhs`, where `rhs` is projected over the values of `lhs`
+    ObjectProjection {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    /// `lhs[].rhs`, where `rhs` is projected over the elements of `lhs`, with those which are arrays flattened into it.
+    /// Unlike the other projections, `lhs` may itself be a projection, which this flattens the results of.
+    FlattenProjection {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    /// `name(args, ...)`, calling one of JMESPath's built-in functions
+    FunctionCall { name: String, args: Vec<Expression> },
+    /// `&expression`, which a function like `sort_by` evaluates against each element it is given.
+    /// Anywhere else, it is `null`.
+    ExprRef(Box<Expression>),
+    /// `{key: expression, ...}`, an object of each expression evaluated against the current node,
+    /// or `null` if that is `null`
+    MultiSelectHash(Vec<(String, Expression)>),
+}
+
+/// Dropping is iterative rather than recursive, so deeply nested expressions don't overflow the stack.
+impl Drop for Expression {
+    fn drop(&mut self) {
+        let mut stack = Vec::new();
+        self.take_children(&mut stack);
+        // Each child is dropped with its own children already taken, so this never recurses
+        while let Some(mut expression) = stack.pop() {
+            expression.take_children(&mut stack);
+        }
+    }
+}
+
+/// The indices a [`Expression::SliceProjection`] last resolved, and the array length they were for.
+///
+/// Batches of same-length arrays are common, so this saves resolving the slice for each one.
+/// It takes no part in comparisons, and isn't cloned.
+#[derive(Default)]
+pub struct SliceCache(pub(crate) Mutex<Option<(usize, Vec<usize>)>>);
+
+impl fmt::Debug for SliceCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SliceCache")
+    }
+}
+
+impl Clone for SliceCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl PartialEq for SliceCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for SliceCache {}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Comparator {
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+}
+
+impl fmt::Display for Comparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Comparator::Equal => "==",
+            Comparator::NotEqual => "!=",
+            Comparator::LessThan => "<",
+            Comparator::LessThanOrEqual => "<=",
+            Comparator::GreaterThan => ">",
+            Comparator::GreaterThanOrEqual => ">=",
+        })
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq, Hash, Clone)]
+pub enum ParseExpressionError {
+    #[error("Unexpected character {character:?} at {position}")]
+    UnexpectedCharacter { character: char, position: usize },
+    #[error("Unexpected token at {0}")]
+    UnexpectedToken(usize),
+    #[error("Unexpected end of expression")]
+    UnexpectedEnd,
+    #[error("Invalid quoted identifier at {0}")]
+    InvalidQuotedIdentifier(usize),
+    #[error("Invalid number at {0}")]
+    InvalidNumber(usize),
+    #[error("Invalid literal at {0}")]
+    InvalidLiteral(usize),
+    #[error("Unterminated raw string at {0}")]
+    UnterminatedRawString(usize),
+    #[error("Slice step not allowed to be zero at {0}")]
+    ZeroSliceStep(usize),
+}
+
+impl Expression {
+    /// Move this expression's children onto `stack`, leaving [`Expression::Current`] in their place.
+    fn take_children(&mut self, stack: &mut Vec<Expression>) {
+        let mut take = |expression: &mut Box<Expression>| {
+            stack.push(mem::replace(&mut **expression, Expression::Current))
+        };
+        match self {
+            Expression::Current
+            | Expression::Identifier(_)
+            | Expression::Index(_)
+            | Expression::Variable(_)
+            | Expression::Literal(_) => {}
+            Expression::Not(expression) | Expression::ExprRef(expression) => take(expression),
+            Expression::Subexpression(lhs, rhs)
+            | Expression::Comparison(_, lhs, rhs)
+            | Expression::Or(lhs, rhs)
+            | Expression::And(lhs, rhs)
+            | Expression::Pipe(lhs, rhs)
+            | Expression::SliceProjection { lhs, rhs, .. }
+            | Expression::ObjectProjection { lhs, rhs }
+            | Expression::FlattenProjection { lhs, rhs } => {
+                take(lhs);
+                take(rhs);
+            }
+            Expression::FilterProjection {
+                lhs,
+                condition,
+                rhs,
+            } => {
+                take(lhs);
+                take(condition);
+                take(rhs);
+            }
+            Expression::FunctionCall { args, .. } => stack.append(args),
+            Expression::MultiSelectHash(entries) => {
+                stack.extend(entries.drain(..).map(|(_, expression)| expression))
+            }
+        }
+    }
+
+    /// Whether this prints starting with a `[`, so shouldn't be preceded by a `.`
+    fn is_bracketed(&self) -> bool {
+        match self {
+            Expression::Index(_) => true,
+            // Rather than `@`, projections elide a current `lhs`
+            Expression::FilterProjection { lhs, .. }
+            | Expression::SliceProjection { lhs, .. }
+            | Expression::FlattenProjection { lhs, .. }
+                if **lhs == Expression::Current =>
+            {
+                true
+            }
+            // Unless it is parenthesized
+            Expression::Subexpression(lhs, _)
+            | Expression::FilterProjection { lhs, .. }
+            | Expression::SliceProjection { lhs, .. } => {
+                lhs.precedence() == u8::MAX && lhs.is_bracketed()
+            }
+            Expression::FlattenProjection { lhs, .. } => {
+                lhs.precedence() >= 9 && lhs.is_bracketed()
+            }
+            Expression::Current
+            | Expression::Identifier(_)
+            | Expression::Variable(_)
+            | Expression::Literal(_)
+            | Expression::FunctionCall { .. }
+            | Expression::ExprRef(_)
+            | Expression::MultiSelectHash(_)
+            | Expression::Comparison(..)
+            | Expression::Not(_)
+            | Expression::Or(..)
+            | Expression::And(..)
+            | Expression::Pipe(..)
+            | Expression::ObjectProjection { .. } => false,
+        }
+    }
+
+    /// How loosely this binds when printed, matching [`Token::binding_power`].
+    /// Subexpressions of a lower precedence than their position requires are parenthesized.
+    fn precedence(&self) -> u8 {
+        match self {
+            // Takes everything after it
+            Expression::ExprRef(_) => 0,
+            Expression::Pipe(..) => 1,
+            Expression::Or(..) => 2,
+            Expression::And(..) => 3,
+            Expression::Comparison(..) => 5,
+            Expression::Not(_) => 45,
+            Expression::FilterProjection { .. }
+            | Expression::SliceProjection { .. }
+            | Expression::ObjectProjection { .. } => 10,
+            // Looser than the other projections, so it can flatten them
+            Expression::FlattenProjection { .. } => 9,
+            Expression::Current
+            | Expression::Identifier(_)
+            | Expression::Index(_)
+            | Expression::Subexpression(..)
+            | Expression::Variable(_)
+            | Expression::Literal(_)
+            | Expression::FunctionCall { .. }
+            | Expression::MultiSelectHash(_) => u8::MAX,
+        }
+    }
+}
+
+/// Print `operand`, parenthesized if it has a lower precedence than `min`
+fn write_operand(f: &mut fmt::Formatter<'_>, operand: &Expression, min: u8) -> fmt::Result {
+    match operand.precedence() < min {
+        true => write!(f, "({})", operand),
+        false => write!(f, "{}", operand),
+    }
+}
+
+/// Print the `rhs` of a projection, which is implicit if it is `@`
+fn write_projection_rhs(f: &mut fmt::Formatter<'_>, rhs: &Expression) -> fmt::Result {
+    match rhs {
+        Expression::Current => Ok(()),
+        rhs if rhs.is_bracketed() => write!(f, "{}", rhs),
+        rhs => write!(f, ".{}", rhs),
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Current => f.write_str("@"),
+            Expression::Identifier(name) => write_identifier(f, name),
+            Expression::Index(index) => write!(f, "[{}]", index),
+            Expression::Subexpression(lhs, rhs) => {
+                // A projection on the left would otherwise swallow `rhs`
+                write_operand(f, lhs, u8::MAX)?;
+                if !rhs.is_bracketed() {
+                    f.write_str(".")?;
+                }
+                write!(f, "{}", rhs)
+            }
+            Expression::Variable(name) => write!(f, "${}", name),
+            Expression::Literal(value) => write!(
+                f,
+                "`{}`",
+                serde_json::to_string(value)
+                    .expect("Values always serialize")
+                    .replace('`', "\\`")
+            ),
+            Expression::Comparison(comparator, lhs, rhs) => {
+                write_operand(f, lhs, 5)?;
+                write!(f, " {} ", comparator)?;
+                write_operand(f, rhs, 6)
+            }
+            Expression::Not(expression) => match **expression {
+                Expression::Current
+                | Expression::Identifier(_)
+                | Expression::Index(_)
+                | Expression::Variable(_)
+                | Expression::Literal(_)
+                | Expression::FunctionCall { .. }
+                | Expression::MultiSelectHash(_)
+                | Expression::Not(_) => write!(f, "!{}", expression),
+                // Which would otherwise bind looser than the `!`
+                _ => write!(f, "!({})", expression),
+            },
+            Expression::Or(lhs, rhs) => {
+                write_operand(f, lhs, 2)?;
+                f.write_str(" || ")?;
+                write_operand(f, rhs, 3)
+            }
+            Expression::And(lhs, rhs) => {
+                write_operand(f, lhs, 3)?;
+                f.write_str(" && ")?;
+                write_operand(f, rhs, 4)
+            }
+            Expression::Pipe(lhs, rhs) => {
+                write_operand(f, lhs, 1)?;
+                f.write_str(" | ")?;
+                write_operand(f, rhs, 2)
+            }
+            Expression::FilterProjection {
+                lhs,
+                condition,
+                rhs,
+            } => {
+                if **lhs != Expression::Current {
+                    write_operand(f, lhs, u8::MAX)?;
+                }
+                write!(f, "[?{}]", condition)?;
+                write_projection_rhs(f, rhs)
+            }
+            Expression::SliceProjection {
+                lhs, slice, rhs, ..
+            } => {
+                if **lhs != Expression::Current {
+                    write_operand(f, lhs, u8::MAX)?;
+                }
+                write!(f, "[{}]", slice)?;
+                write_projection_rhs(f, rhs)
+            }
+            Expression::ObjectProjection { lhs, rhs } => {
+                if **lhs != Expression::Current {
+                    write_operand(f, lhs, u8::MAX)?;
+                    f.write_str(".")?;
+                }
+                f.write_str("*")?;
+                write_projection_rhs(f, rhs)
+            }
+            Expression::FlattenProjection { lhs, rhs } => {
+                if **lhs != Expression::Current {
+                    write_operand(f, lhs, 9)?;
+                }
+                f.write_str("[]")?;
+                write_projection_rhs(f, rhs)
+            }
+            Expression::FunctionCall { name, args } => {
+                write!(f, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                f.write_str(")")
+            }
+            Expression::ExprRef(expression) => write!(f, "&{}", expression),
+            Expression::MultiSelectHash(entries) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    write_identifier(f, key)?;
+                    write!(f, ": {}", value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+/// `unquoted-string` if the grammar allows it, else `quoted-string`.
+fn write_identifier(f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+    if lazy_regex::regex_is_match!(r"^[A-Za-z_][A-Za-z0-9_]*$", name) {
+        f.write_str(name)
+    } else {
+        // JMESPath's quoted-string is a JSON string
+        f.write_str(&serde_json::to_string(name).expect("Strings always serialize"))
+    }
+}
+
+impl str::FromStr for Expression {
+    type Err = ParseExpressionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?.into_iter().peekable(),
+        };
+        let expression = parser.expression(0)?;
+        match parser.next() {
+            (_, Token::End) => Ok(expression),
+            (position, _) => Err(ParseExpressionError::UnexpectedToken(position)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Identifier(String),
+    QuotedIdentifier(String),
+    Number(isize),
+    Variable(String),
+    Literal(Value),
+    Comparator(Comparator),
+    /// `||`
+    Or,
+    /// `&&`
+    And,
+    /// `!`
+    Not,
+    /// `&`, starting an expression reference
+    Ampersand,
+    Pipe,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Comma,
+    Star,
+    At,
+    Dot,
+    LBracket,
+    /// `[?`
+    Filter,
+    /// `[]`
+    Flatten,
+    RBracket,
+    Colon,
+    End,
+}
+
+impl Token {
+    /// How tightly this token binds to the expression on its left.
+    fn binding_power(&self) -> u8 {
+        match self {
+            Token::Pipe => 1,
+            Token::Or => 2,
+            Token::And => 3,
+            Token::Comparator(_) => 5,
+            Token::Flatten => 9,
+            Token::Filter => 21,
+            Token::Dot => 40,
+            Token::LBracket => 55,
+            _ => 0,
+        }
+    }
+}
+
+/// Tokens with their byte offset into `s`, always ending with [`Token::End`].
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, ParseExpressionError> {
+    use ParseExpressionError::{
+        InvalidLiteral, InvalidNumber, InvalidQuotedIdentifier, UnexpectedCharacter,
+        UnterminatedRawString,
+    };
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        let token = match c {
+            c if c.is_ascii_whitespace() => continue,
+            '@' => Token::At,
+            '.' => Token::Dot,
+            '[' if chars.next_if(|(_, c)| *c == '?').is_some() => Token::Filter,
+            '[' if chars.next_if(|(_, c)| *c == ']').is_some() => Token::Flatten,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            ':' => Token::Colon,
+            '*' => Token::Star,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            ',' => Token::Comma,
+            '|' if chars.next_if(|(_, c)| *c == '|').is_some() => Token::Or,
+            '|' => Token::Pipe,
+            '&' if chars.next_if(|(_, c)| *c == '&').is_some() => Token::And,
+            '&' => Token::Ampersand,
+            '<' if chars.next_if(|(_, c)| *c == '=').is_some() => {
+                Token::Comparator(Comparator::LessThanOrEqual)
+            }
+            '<' => Token::Comparator(Comparator::LessThan),
+            '>' if chars.next_if(|(_, c)| *c == '=').is_some() => {
+                Token::Comparator(Comparator::GreaterThanOrEqual)
+            }
+            '>' => Token::Comparator(Comparator::GreaterThan),
+            '=' if chars.next_if(|(_, c)| *c == '=').is_some() => {
+                Token::Comparator(Comparator::Equal)
+            }
+            '!' if chars.next_if(|(_, c)| *c == '=').is_some() => {
+                Token::Comparator(Comparator::NotEqual)
+            }
+            '!' => Token::Not,
+            'a'..='z' | 'A'..='Z' | '_' => Token::Identifier(unquoted(c, &mut chars)),
+            '$' => match chars.next_if(|(_, c)| c.is_ascii_alphabetic() || *c == '_') {
+                Some((_, c)) => Token::Variable(unquoted(c, &mut chars)),
+                None => {
+                    return Err(UnexpectedCharacter {
+                        character: '$',
+                        position,
+                    })
+                }
+            },
+            '-' | '0'..='9' => {
+                let mut number = String::from(c);
+                while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                    number.push(c)
+                }
+                Token::Number(number.parse().map_err(|_| InvalidNumber(position))?)
+            }
+            '"' => {
+                // Find the closing quote, then let serde_json handle the escapes
+                let mut end = None;
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => {
+                            end = Some(i);
+                            break;
+                        }
+                        _ => (),
+                    }
+                }
+                let end = end.ok_or(InvalidQuotedIdentifier(position))?;
+                Token::QuotedIdentifier(
+                    serde_json::from_str(&s[position..=end])
+                        .map_err(|_| InvalidQuotedIdentifier(position))?,
+                )
+            }
+            '`' => {
+                // Only backticks are escaped, the rest is JSON
+                let mut json = String::new();
+                loop {
+                    match chars.next().ok_or(InvalidLiteral(position))? {
+                        (_, '\\') if chars.next_if(|(_, c)| *c == '`').is_some() => json.push('`'),
+                        (_, '`') => break,
+                        (_, c) => json.push(c),
+                    }
+                }
+                Token::Literal(serde_json::from_str(&json).map_err(|_| InvalidLiteral(position))?)
+            }
+            '\'' => {
+                // Only single quotes are escaped, so `'\n'` is a backslash and an `n`
+                let mut raw = String::new();
+                loop {
+                    match chars.next().ok_or(UnterminatedRawString(position))? {
+                        (_, '\\') if chars.next_if(|(_, c)| *c == '\'').is_some() => raw.push('\''),
+                        (_, '\'') => break,
+                        (_, c) => raw.push(c),
+                    }
+                }
+                Token::Literal(Value::String(raw))
+            }
+            character => {
+                return Err(UnexpectedCharacter {
+                    character,
+                    position,
+                })
+            }
+        };
+        tokens.push((position, token));
+    }
+    tokens.push((s.len(), Token::End));
+    Ok(tokens)
+}
+
+/// The rest of an `unquoted-string` starting with `first`
+fn unquoted(first: char, chars: &mut Peekable<str::CharIndices>) -> String {
+    let mut identifier = String::from(first);
+    while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_') {
+        identifier.push(c)
+    }
+    identifier
+}
+
+/// A Pratt parser, with binding powers from [`Token::binding_power`].
+struct Parser {
+    tokens: Peekable<vec::IntoIter<(usize, Token)>>,
+}
+
+impl Parser {
+    fn peek(&mut self) -> &Token {
+        &self.tokens.peek().expect("Always ends with Token::End").1
+    }
+
+    fn next(&mut self) -> (usize, Token) {
+        self.tokens.next().expect("Always ends with Token::End")
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseExpressionError> {
+        match self.next() {
+            (_, token) if token == expected => Ok(()),
+            (position, token) => Err(unexpected(position, token)),
+        }
+    }
+
+    fn expression(&mut self, binding_power: u8) -> Result<Expression, ParseExpressionError> {
+        let left = self.nud()?;
+        self.continue_expression(left, binding_power)
+    }
+
+    fn continue_expression(
+        &mut self,
+        mut left: Expression,
+        binding_power: u8,
+    ) -> Result<Expression, ParseExpressionError> {
+        while binding_power < self.peek().binding_power() {
+            left = self.led(left)?;
+        }
+        Ok(left)
+    }
+
+    /// Parse a token which starts an expression
+    fn nud(&mut self) -> Result<Expression, ParseExpressionError> {
+        match self.next() {
+            (_, Token::Identifier(name)) if *self.peek() == Token::LParen => {
+                self.function_call(name)
+            }
+            (_, Token::Identifier(name) | Token::QuotedIdentifier(name)) => {
+                Ok(Expression::Identifier(name))
+            }
+            (_, Token::At) => Ok(Expression::Current),
+            (_, Token::Variable(name)) => Ok(Expression::Variable(name)),
+            (_, Token::Literal(value)) => Ok(Expression::Literal(value)),
+            (_, Token::LBracket) => self.bracket(Expression::Current),
+            (_, Token::Filter) => self.filter(Expression::Current),
+            (_, Token::Flatten) => self.flatten_projection(Expression::Current),
+            (_, Token::Star) => self.object_projection(Expression::Current),
+            // Binds tighter than `.`, but looser than `[`, like the reference implementation
+            (_, Token::Not) => Ok(Expression::Not(Box::new(self.expression(45)?))),
+            (_, Token::Ampersand) => Ok(Expression::ExprRef(Box::new(self.expression(0)?))),
+            (_, Token::LBrace) => self.multiselect_hash(),
+            (_, Token::LParen) => {
+                let expression = self.expression(0)?;
+                self.expect(Token::RParen)?;
+                Ok(expression)
+            }
+            (position, token) => Err(unexpected(position, token)),
+        }
+    }
+
+    /// Parse a token which continues the expression on its `left`
+    fn led(&mut self, left: Expression) -> Result<Expression, ParseExpressionError> {
+        let right = match self.next() {
+            (_, Token::Dot) if *self.peek() == Token::Star => {
+                self.next();
+                return self.object_projection(left);
+            }
+            (_, Token::Dot) => self.dot_rhs()?,
+            (_, Token::LBracket) => return self.bracket(left),
+            (_, Token::Filter) => return self.filter(left),
+            (_, Token::Flatten) => return self.flatten_projection(left),
+            (_, Token::Comparator(comparator)) => {
+                let right = self.expression(Token::Comparator(comparator).binding_power())?;
+                return Ok(Expression::Comparison(
+                    comparator,
+                    Box::new(left),
+                    Box::new(right),
+                ));
+            }
+            (_, token @ (Token::Or | Token::And | Token::Pipe)) => {
+                // Left associative
+                let right = Box::new(self.expression(token.binding_power())?);
+                let left = Box::new(left);
+                return Ok(match token {
+                    Token::Or => Expression::Or(left, right),
+                    Token::And => Expression::And(left, right),
+                    _ => Expression::Pipe(left, right),
+                });
+            }
+            _ => unreachable!("Only called for tokens with a binding power"),
+        };
+        Ok(Expression::Subexpression(Box::new(left), Box::new(right)))
+    }
+
+    fn dot_rhs(&mut self) -> Result<Expression, ParseExpressionError> {
+        match self.next() {
+            (_, Token::Identifier(name)) if *self.peek() == Token::LParen => {
+                self.function_call(name)
+            }
+            (_, Token::Identifier(name) | Token::QuotedIdentifier(name)) => {
+                Ok(Expression::Identifier(name))
+            }
+            (_, Token::LBrace) => self.multiselect_hash(),
+            (position, token) => Err(unexpected(position, token)),
+        }
+    }
+
+    /// Parse the entries of a multiselect hash, after the `{`
+    fn multiselect_hash(&mut self) -> Result<Expression, ParseExpressionError> {
+        let mut entries = Vec::new();
+        loop {
+            let key = match self.next() {
+                (_, Token::Identifier(key) | Token::QuotedIdentifier(key)) => key,
+                (position, token) => return Err(unexpected(position, token)),
+            };
+            self.expect(Token::Colon)?;
+            entries.push((key, self.expression(0)?));
+            match self.next() {
+                (_, Token::Comma) => (),
+                (_, Token::RBrace) => break,
+                (position, token) => return Err(unexpected(position, token)),
+            }
+        }
+        Ok(Expression::MultiSelectHash(entries))
+    }
+
+    /// Parse the arguments of a call to `name`, starting at the `(`
+    fn function_call(&mut self, name: String) -> Result<Expression, ParseExpressionError> {
+        self.expect(Token::LParen)?;
+        let mut args = Vec::new();
+        if *self.peek() == Token::RParen {
+            self.next();
+        } else {
+            loop {
+                args.push(self.expression(0)?);
+                match self.next() {
+                    (_, Token::Comma) => (),
+                    (_, Token::RParen) => break,
+                    (position, token) => return Err(unexpected(position, token)),
+                }
+            }
+        }
+        Ok(Expression::FunctionCall { name, args })
+    }
+
+    /// Parse the rest of a filter projection over `lhs`, after the `[?`
+    fn filter(&mut self, lhs: Expression) -> Result<Expression, ParseExpressionError> {
+        let condition = self.expression(0)?;
+        self.expect(Token::RBracket)?;
+        let rhs = self.projection_rhs(Token::Filter.binding_power())?;
+        Ok(Expression::FilterProjection {
+            lhs: Box::new(lhs),
+            condition: Box::new(condition),
+            rhs: Box::new(rhs),
+        })
+    }
+
+    /// Parse the rest of an object projection over `lhs`, after the `*`
+    fn object_projection(&mut self, lhs: Expression) -> Result<Expression, ParseExpressionError> {
+        let rhs = self.projection_rhs(20)?;
+        Ok(Expression::ObjectProjection {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        })
+    }
+
+    /// Parse the rest of a flatten projection over `lhs`, after the `[]`
+    fn flatten_projection(&mut self, lhs: Expression) -> Result<Expression, ParseExpressionError> {
+        let rhs = self.projection_rhs(Token::Flatten.binding_power())?;
+        Ok(Expression::FlattenProjection {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        })
+    }
+
+    /// Parse what a projection projects, which continues until a token binding looser than `binding_power`
+    fn projection_rhs(&mut self, binding_power: u8) -> Result<Expression, ParseExpressionError> {
+        match self.peek() {
+            // Tokens which stop the projection
+            token if token.binding_power() < 10 => Ok(Expression::Current),
+            Token::LBracket | Token::Filter => self.expression(binding_power),
+            Token::Dot => {
+                self.next();
+                let rhs = match self.peek() {
+                    Token::Star => {
+                        self.next();
+                        self.object_projection(Expression::Current)?
+                    }
+                    _ => self.dot_rhs()?,
+                };
+                self.continue_expression(rhs, binding_power)
+            }
+            _ => {
+                let (position, token) = self.next();
+                Err(unexpected(position, token))
+            }
+        }
+    }
+
+    /// Parse the rest of a bracket specifier on `lhs`, after the `[`
+    fn bracket(&mut self, lhs: Expression) -> Result<Expression, ParseExpressionError> {
+        // `[*]`, which is the same as `[:]` for arrays, and `null` otherwise
+        if *self.peek() == Token::Star {
+            self.next();
+            self.expect(Token::RBracket)?;
+            return self.slice_projection(lhs, JMESSlice::default());
+        }
+        // Up to three optional numbers, separated by up to two colons
+        let mut parts = [None; 3];
+        let mut colons = 0;
+        let close = loop {
+            match self.next() {
+                (_, Token::Number(n)) if parts[colons].is_none() => parts[colons] = Some(n),
+                (_, Token::Colon) if colons < 2 => colons += 1,
+                (position, Token::RBracket) => break position,
+                (position, token) => return Err(unexpected(position, token)),
+            }
+        };
+        match (colons, parts) {
+            (0, [Some(index), ..]) => Ok(match lhs {
+                Expression::Current => Expression::Index(index),
+                lhs => Expression::Subexpression(Box::new(lhs), Box::new(Expression::Index(index))),
+            }),
+            (0, _) => Err(ParseExpressionError::UnexpectedToken(close)),
+            (_, [start, end, step]) => {
+                let slice = JMESSlice {
+                    start,
+                    end,
+                    step: match step {
+                        Some(step) => Some(
+                            NonZeroIsize::new(step)
+                                .ok_or(ParseExpressionError::ZeroSliceStep(close))?,
+                        ),
+                        None => None,
+                    },
+                };
+                self.slice_projection(lhs, slice)
+            }
+        }
+    }
+
+    /// Parse the rest of a slice projection over `lhs`, after the `]`
+    fn slice_projection(
+        &mut self,
+        lhs: Expression,
+        slice: JMESSlice,
+    ) -> Result<Expression, ParseExpressionError> {
+        let rhs = self.projection_rhs(20)?;
+        Ok(Expression::SliceProjection {
+            lhs: Box::new(lhs),
+            slice,
+            rhs: Box::new(rhs),
+            cache: SliceCache::default(),
+        })
+    }
+}
+
+fn unexpected(position: usize, token: Token) -> ParseExpressionError {
+    match token {
+        Token::End => ParseExpressionError::UnexpectedEnd,
+        _ => ParseExpressionError::UnexpectedToken(position),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Expression::{Comparison, Current, FilterProjection, Identifier, Index, Subexpression};
+
+    fn sub(lhs: Expression, rhs: Expression) -> Expression {
+        Subexpression(Box::new(lhs), Box::new(rhs))
+    }
+
+    fn identifier(name: &str) -> Expression {
+        Identifier(name.into())
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "a.b[0]".parse(),
+            Ok(sub(sub(identifier("a"), identifier("b")), Index(0)))
+        );
+        assert_eq!("[-1]".parse(), Ok(Index(-1)));
+        assert_eq!(
+            r#" "weird key" . b "#.parse(),
+            Ok(sub(identifier("weird key"), identifier("b")))
+        );
+        assert_eq!(
+            "a.".parse::<Expression>(),
+            Err(ParseExpressionError::UnexpectedEnd)
+        );
+        assert_eq!(
+            "a b".parse::<Expression>(),
+            Err(ParseExpressionError::UnexpectedToken(2))
+        );
+        assert_eq!(
+            "a.1".parse::<Expression>(),
+            Err(ParseExpressionError::UnexpectedToken(2))
+        );
+        assert_eq!(
+            "a[99999999999999999999:0]".parse::<Expression>(),
+            Err(ParseExpressionError::InvalidNumber(2))
+        );
+        assert_eq!(
+            r#""unterminated"#.parse::<Expression>(),
+            Err(ParseExpressionError::InvalidQuotedIdentifier(0))
+        );
+    }
+
+    #[test]
+    fn parse_quoted_identifier_escapes() {
+        for (s, key) in [
+            (r#""a\"b""#, "a\"b"),
+            (r#""a\\b""#, r"a\b"),
+            (r#""a\/b""#, "a/b"),
+            (r#""a\nb""#, "a\nb"),
+            (r#""a\tb""#, "a\tb"),
+            (r#""a\rb\bc\fd""#, "a\rb\u{8}c\u{c}d"),
+            (r#""\u00e9""#, "é"),
+            (r#""\ud83d\ude00""#, "😀"),
+        ] {
+            assert_eq!(s.parse(), Ok(identifier(key)), "{}", s);
+        }
+        assert_eq!(
+            r#"a."\x""#.parse::<Expression>(),
+            Err(ParseExpressionError::InvalidQuotedIdentifier(2))
+        );
+        assert_eq!(
+            r#""\"#.parse::<Expression>(),
+            Err(ParseExpressionError::InvalidQuotedIdentifier(0))
+        );
+    }
+
+    #[test]
+    fn parse_filter_projection() {
+        assert_eq!(
+            "items[?price < $max].name".parse(),
+            Ok(FilterProjection {
+                lhs: Box::new(identifier("items")),
+                condition: Box::new(Comparison(
+                    Comparator::LessThan,
+                    Box::new(identifier("price")),
+                    Box::new(Expression::Variable("max".into()))
+                )),
+                rhs: Box::new(identifier("name")),
+            })
+        );
+        assert_eq!(
+            "[?@ == @]".parse(),
+            Ok(FilterProjection {
+                lhs: Box::new(Current),
+                condition: Box::new(Comparison(
+                    Comparator::Equal,
+                    Box::new(Current),
+                    Box::new(Current)
+                )),
+                rhs: Box::new(Current),
+            })
+        );
+        assert_eq!(
+            "a[?b]c".parse::<Expression>(),
+            Err(ParseExpressionError::UnexpectedToken(5))
+        );
+        assert_eq!(
+            "$".parse::<Expression>(),
+            Err(ParseExpressionError::UnexpectedCharacter {
+                character: '$',
+                position: 0
+            })
+        );
+    }
+
+    #[test]
+    fn parse_flatten_projection() {
+        let flatten = |lhs, rhs| Expression::FlattenProjection {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        };
+        assert_eq!(
+            "a[].b".parse(),
+            Ok(flatten(identifier("a"), identifier("b")))
+        );
+        // Flattens the projection on its left, rather than being projected by it
+        assert_eq!(
+            "a[*].b[].c".parse(),
+            Ok(flatten(
+                Expression::SliceProjection {
+                    lhs: Box::new(identifier("a")),
+                    slice: JMESSlice::default(),
+                    rhs: Box::new(identifier("b")),
+                    cache: SliceCache::default(),
+                },
+                identifier("c")
+            ))
+        );
+        assert_eq!("[]".parse(), Ok(flatten(Current, Current)));
+        assert_eq!(
+            "a[ ]".parse::<Expression>(),
+            Err(ParseExpressionError::UnexpectedToken(3))
+        );
+    }
+
+    #[test]
+    fn parse_literals() {
+        assert_eq!("`null`".parse(), Ok(Expression::Literal(Value::Null)));
+        assert_eq!(
+            r#"` {"a": "\`"} `"#.parse(),
+            Ok(Expression::Literal(serde_json::json!({"a": "`"})))
+        );
+        assert_eq!(
+            "a == `nul`".parse::<Expression>(),
+            Err(ParseExpressionError::InvalidLiteral(5))
+        );
+        assert_eq!(
+            "`1".parse::<Expression>(),
+            Err(ParseExpressionError::InvalidLiteral(0))
+        );
+    }
+
+    #[test]
+    fn parse_raw_strings() {
+        let raw = |s: &str| Ok(Expression::Literal(Value::String(s.into())));
+        assert_eq!(r"'it\'s raw'".parse(), raw("it's raw"));
+        assert_eq!(r"'\t'".parse(), raw(r"\t"));
+        assert_eq!(r"'a\\b'".parse(), raw(r"a\\b"));
+        assert_eq!(r#"'"`'"#.parse(), raw(r#""`"#));
+        assert_eq!("''".parse(), raw(""));
+        assert_eq!(
+            r"a == 'b\'".parse::<Expression>(),
+            Err(ParseExpressionError::UnterminatedRawString(5))
+        );
+    }
+
+    #[test]
+    fn parse_function_calls() {
+        let call = |name: &str, args| Expression::FunctionCall {
+            name: name.into(),
+            args,
+        };
+        assert_eq!("f()".parse(), Ok(call("f", vec![])));
+        assert_eq!(
+            "a.f(@, b || c)[0]".parse(),
+            Ok(sub(
+                sub(
+                    identifier("a"),
+                    call(
+                        "f",
+                        vec![
+                            Current,
+                            Expression::Or(Box::new(identifier("b")), Box::new(identifier("c")))
+                        ]
+                    )
+                ),
+                Index(0)
+            ))
+        );
+        // Quoted identifiers aren't function names
+        assert_eq!(
+            r#""f"(a)"#.parse::<Expression>(),
+            Err(ParseExpressionError::UnexpectedToken(3))
+        );
+        assert_eq!(
+            "f(a,)".parse::<Expression>(),
+            Err(ParseExpressionError::UnexpectedToken(4))
+        );
+        assert_eq!(
+            "f(a".parse::<Expression>(),
+            Err(ParseExpressionError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn parse_multiselect_hash() {
+        assert_eq!(
+            "a[*].{b: c, \"d e\": f[0]}".parse(),
+            Ok(Expression::SliceProjection {
+                lhs: Box::new(identifier("a")),
+                slice: JMESSlice::default(),
+                rhs: Box::new(Expression::MultiSelectHash(vec![
+                    ("b".into(), identifier("c")),
+                    ("d e".into(), sub(identifier("f"), Index(0)))
+                ])),
+                cache: SliceCache::default(),
+            })
+        );
+        assert_eq!(
+            "{}".parse::<Expression>(),
+            Err(ParseExpressionError::UnexpectedToken(1))
+        );
+        assert_eq!(
+            "{a: b,}".parse::<Expression>(),
+            Err(ParseExpressionError::UnexpectedToken(6))
+        );
+        assert_eq!(
+            "{a}".parse::<Expression>(),
+            Err(ParseExpressionError::UnexpectedToken(2))
+        );
+        assert_eq!(
+            "a[*b]".parse::<Expression>(),
+            Err(ParseExpressionError::UnexpectedToken(3))
+        );
+    }
+
+    #[test]
+    fn display_quotes_identifiers() {
+        assert_eq!(identifier("weird key").to_string(), r#""weird key""#);
+        assert_eq!(identifier("1st").to_string(), r#""1st""#);
+        assert_eq!(identifier(r#"say "hi""#).to_string(), r#""say \"hi\"""#);
+        assert_eq!(identifier("_snake_case1").to_string(), "_snake_case1");
+        let expression = sub(sub(identifier("a"), Index(0)), identifier("weird key"));
+        assert_eq!(expression.to_string(), r#"a[0]."weird key""#);
+        assert_eq!(expression.to_string().parse(), Ok(expression));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for s in [
+            "a",
+            "a.b.c",
+            "[0]",
+            "[0][-1].a",
+            r#"a."b.c"[2]"#,
+            "@",
+            "a[?b != $c].d[0]",
+            "[?a >= b][?c <= @][0]",
+            "a[?b > c][?d]",
+            "a[1:2].b",
+            "[::-1][0]",
+            "a[:-1:2][?b][1:]",
+            "a || b && c",
+            "(a || b) && c",
+            "(a || b).c",
+            "a | b | c",
+            "a | (b | c)",
+            "a[?b || c].d | [0]",
+            "(a[?b].c).d",
+            "a < (b == c)",
+            "(a[?b])[?c]",
+            "a.*.b",
+            "*.b[0]",
+            "a[?b].*.c",
+            "a.*[?b][0]",
+            "(a.*).b",
+            "!a",
+            "!!a || !$b",
+            "!(a[0]) == !(a.b)",
+            "(!a).b",
+            "[?!a]",
+            "[?!(a == b)].c",
+            "`null`",
+            "a[?b == `{\"c\":[1,\"\\`\"]}`]",
+            "length(a)",
+            "a.join(`\", \"`, b[?c])[0]",
+            "merge(@, `{}`)",
+            "type(a | b)",
+            "!not_null(a, b)",
+            "a[:].length(@)",
+            "sort_by(a, &b.c)",
+            "map(&a || b, c)",
+            "max_by(a, &b | c)",
+            "(&a).b",
+            "!(&a)",
+            "{a: b, \"c d\": e[0]}",
+            "a[:].{b: c || d, e: f | g}",
+            "a.{b: {c: d}}.b",
+            "!{a: b}",
+            "sort(keys(a))[0]",
+            "f(a)[1:].b",
+            "f(a).b[?c]",
+            "a[].b",
+            "[]",
+            "a[][]",
+            "a[:].b[].c",
+            "a[?b].c[][0]",
+            "(a[])[:]",
+            "(a || b)[]",
+            "a[] || b",
+            "!(a[])",
+        ] {
+            let expression = s.parse::<Expression>().unwrap();
+            assert_eq!(expression.to_string(), s);
+        }
+        // Right-nested trees print without parentheses, so they re-parse left-nested.
+        // That loses the associativity, but not the meaning
+        let expression = sub(identifier("a"), sub(Index(0), identifier("b")));
+        assert_eq!(expression.to_string(), "a[0].b");
+        assert_eq!(
+            "a[0].b".parse::<Expression>().unwrap(),
+            sub(sub(identifier("a"), Index(0)), identifier("b"))
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! Converting expressions parsed by the [`jmespath`] crate, for migrating from it.
+//!
+//! | [`Ast`]                                  | [`Expression`]                            |
+//! |------------------------------------------|-------------------------------------------|
+//! | `Identity`                               | [`Expression::Current`]                   |
+//! | `Field`                                  | [`Expression::Identifier`]                |
+//! | `Index`                                  | [`Expression::Index`]                     |
+//! | `Literal`                                | [`Expression::Literal`]                   |
+//! | `Comparison`                             | [`Expression::Comparison`]                |
+//! | `Not`, `Or`, `And`                       | [`Expression::Not`], `Or`, `And`          |
+//! | `Subexpr`                                | [`Expression::Subexpression`], or [`Expression::Pipe`] after a projection |
+//! | `Projection` of a `Slice`, or `[*]`      | [`Expression::SliceProjection`]           |
+//! | `Projection` of a `Condition`            | [`Expression::FilterProjection`]          |
+//! | `Projection` of `ObjectValues`           | [`Expression::ObjectProjection`]          |
+//! | `Projection` of a `Flatten`, or `Flatten` | [`Expression::FlattenProjection`]        |
+//! | `Function`                               | [`Expression::FunctionCall`]              |
+//! | `Expref`                                 | [`Expression::ExprRef`]                   |
+//! | `MultiHash`                              | [`Expression::MultiSelectHash`]           |
+//!
+//! Anything else is an [`UnsupportedAst`].
+
+use crate::{ast::SliceCache, Comparator, Expression, JMESSlice};
+use jmespath::ast::{self, Ast};
+use std::num::NonZeroIsize;
+use thiserror::Error;
+
+/// An [`Ast`] node with no [`Expression`] equivalent.
+#[derive(Debug, Error, PartialEq, Eq, Hash, Clone, Copy)]
+#[error("Unsupported jmespath construct {construct} at {offset}")]
+pub struct UnsupportedAst {
+    /// Which construct, like `"function call"`
+    pub construct: &'static str,
+    /// Where it is in the original expression
+    pub offset: usize,
+}
+
+impl TryFrom<&Ast> for Expression {
+    type Error = UnsupportedAst;
+
+    fn try_from(ast: &Ast) -> Result<Self, Self::Error> {
+        let convert = |ast: &Ast| Expression::try_from(ast).map(Box::new);
+        let unsupported = |construct, offset| Err(UnsupportedAst { construct, offset });
+        Ok(match ast {
+            Ast::Identity { .. } => Expression::Current,
+            Ast::Field { name, .. } => Expression::Identifier(name.clone()),
+            Ast::Index { idx, .. } => Expression::Index(*idx as isize),
+            Ast::Literal { value, .. } => Expression::Literal(
+                serde_json::to_value(&**value).expect("jmespath values are valid JSON"),
+            ),
+            Ast::Comparison {
+                comparator,
+                lhs,
+                rhs,
+                ..
+            } => Expression::Comparison(comparator.into(), convert(lhs)?, convert(rhs)?),
+            Ast::Not { node, .. } => Expression::Not(convert(node)?),
+            Ast::Or { lhs, rhs, .. } => Expression::Or(convert(lhs)?, convert(rhs)?),
+            Ast::And { lhs, rhs, .. } => Expression::And(convert(lhs)?, convert(rhs)?),
+            Ast::Subexpr { lhs, rhs, .. } => {
+                let lhs = convert(lhs)?;
+                // `a[0:1]` is `a` followed by a projection of a slice of the current node
+                let mut rhs = Expression::try_from(&**rhs)?;
+                match &mut rhs {
+                    Expression::SliceProjection { lhs: current, .. }
+                        if **current == Expression::Current =>
+                    {
+                        *current = lhs;
+                        rhs
+                    }
+                    // Only a pipe can follow a projection, as anything else would be projected
+                    _ if matches!(
+                        *lhs,
+                        Expression::FilterProjection { .. }
+                            | Expression::SliceProjection { .. }
+                            | Expression::ObjectProjection { .. }
+                            | Expression::FlattenProjection { .. }
+                    ) =>
+                    {
+                        Expression::Pipe(lhs, Box::new(rhs))
+                    }
+                    _ => Expression::Subexpression(lhs, Box::new(rhs)),
+                }
+            }
+            Ast::Projection { lhs, rhs, .. } => match (&**lhs, &**rhs) {
+                (
+                    Ast::Slice {
+                        offset,
+                        start,
+                        stop,
+                        step,
+                    },
+                    rhs,
+                ) => {
+                    let Some(step) = NonZeroIsize::new(*step as isize) else {
+                        return unsupported("zero slice step", *offset);
+                    };
+                    Expression::SliceProjection {
+                        lhs: Box::new(Expression::Current),
+                        slice: JMESSlice {
+                            start: start.map(|start| start as isize),
+                            end: stop.map(|stop| stop as isize),
+                            // The default, which `jmespath` makes explicit
+                            step: (step.get() != 1).then_some(step),
+                        },
+                        rhs: convert(rhs)?,
+                        cache: SliceCache::default(),
+                    }
+                }
+                (Ast::ObjectValues { node, .. }, rhs) => Expression::ObjectProjection {
+                    lhs: convert(node)?,
+                    rhs: convert(rhs)?,
+                },
+                (Ast::Flatten { node, .. }, rhs) => Expression::FlattenProjection {
+                    lhs: convert(node)?,
+                    rhs: convert(rhs)?,
+                },
+                (
+                    lhs,
+                    Ast::Condition {
+                        predicate, then, ..
+                    },
+                ) => Expression::FilterProjection {
+                    lhs: convert(lhs)?,
+                    condition: convert(predicate)?,
+                    rhs: convert(then)?,
+                },
+                // `[*]`, which is the same as `[:]` for arrays, and `null` otherwise
+                (lhs, rhs) => Expression::SliceProjection {
+                    lhs: convert(lhs)?,
+                    slice: JMESSlice::default(),
+                    rhs: convert(rhs)?,
+                    cache: SliceCache::default(),
+                },
+            },
+            Ast::Condition { offset, .. } => return unsupported("condition", *offset),
+            Ast::Expref { ast, .. } => Expression::ExprRef(convert(ast)?),
+            Ast::Flatten { node, .. } => Expression::FlattenProjection {
+                lhs: convert(node)?,
+                rhs: Box::new(Expression::Current),
+            },
+            Ast::Function { name, args, .. } => Expression::FunctionCall {
+                name: name.clone(),
+                args: args
+                    .iter()
+                    .map(Expression::try_from)
+                    .collect::<Result<_, _>>()?,
+            },
+            Ast::MultiList { offset, .. } => return unsupported("multiselect list", *offset),
+            Ast::MultiHash { elements, .. } => Expression::MultiSelectHash(
+                elements
+                    .iter()
+                    .map(|ast::KeyValuePair { key, value }| {
+                        Ok((key.clone(), Expression::try_from(value)?))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            Ast::ObjectValues { offset, .. } => return unsupported("object values", *offset),
+            Ast::Slice { offset, .. } => return unsupported("slice", *offset),
+        })
+    }
+}
+
+impl From<&ast::Comparator> for Comparator {
+    fn from(comparator: &ast::Comparator) -> Self {
+        match comparator {
+            ast::Comparator::Equal => Comparator::Equal,
+            ast::Comparator::NotEqual => Comparator::NotEqual,
+            ast::Comparator::LessThan => Comparator::LessThan,
+            ast::Comparator::LessThanEqual => Comparator::LessThanOrEqual,
+            ast::Comparator::GreaterThan => Comparator::GreaterThan,
+            ast::Comparator::GreaterThanEqual => Comparator::GreaterThanOrEqual,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn convert(expression: &str) -> Result<Expression, UnsupportedAst> {
+        Expression::try_from(&jmespath::parse(expression).unwrap())
+    }
+
+    #[test]
+    fn convert_and_search() {
+        // `jmespath` nests subexpressions to the right, which is equivalent
+        let expression = convert("a.b[0]").unwrap();
+        assert_eq!(expression.to_string(), "a.b[0]");
+        let data = json!({"a": {"b": ["x", "y"]}});
+        assert_eq!(expression.search(data), Ok(json!("x")));
+    }
+
+    #[test]
+    fn projections() {
+        for expression in [
+            "a[1:].b",
+            "[::-1]",
+            "a.*.b",
+            "a[?b == `1`].c",
+            "a[0:2] | [0]",
+            "!a || b && c",
+            "join(`\", \"`, a[:].b)",
+            "sort_by(a, &b.c)",
+            "a[1:].{b: c, d: e[0]}",
+            "a[].b",
+            "a[*].b[].c",
+            "a[][]",
+        ] {
+            assert_eq!(
+                convert(expression).unwrap().to_string(),
+                expression.parse::<Expression>().unwrap().to_string(),
+            );
+        }
+        assert_eq!(convert("a[*].b").unwrap().to_string(), "a[:].b");
+    }
+
+    #[test]
+    fn unsupported() {
+        assert!(matches!(
+            convert("[a, b]"),
+            Err(UnsupportedAst {
+                construct: "multiselect list",
+                ..
+            })
+        ));
+    }
+}
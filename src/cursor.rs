@@ -0,0 +1,131 @@
+//! Step-by-step navigation of a document, for interactive tools.
+
+use crate::{normalize_index, render_path, type_name, PathError, PathSegment};
+use serde_json::Value::{self, Array, Object};
+
+/// A position within a borrowed document, which can move down into children and back up.
+#[derive(Debug, Clone)]
+pub struct Cursor<'a> {
+    /// Every node from the root to the current one
+    nodes: Vec<&'a Value>,
+    /// How we got from each node to the next
+    path: Vec<PathSegment>,
+}
+
+impl<'a> Cursor<'a> {
+    /// Start at the root of `value`.
+    pub fn new(value: &'a Value) -> Self {
+        Self {
+            nodes: vec![value],
+            path: Vec::new(),
+        }
+    }
+
+    pub fn current(&self) -> &'a Value {
+        self.nodes.last().expect("Always has the root")
+    }
+
+    pub fn path(&self) -> &[PathSegment] {
+        &self.path
+    }
+
+    /// [`Cursor::path`], as a JMESPath expression.
+    pub fn rendered_path(&self) -> String {
+        render_path(&self.path)
+    }
+
+    /// Move to `key` of the current object, staying put if there is none.
+    pub fn down_key(&mut self, key: &str) -> Result<&'a Value, PathError> {
+        let child = match self.current() {
+            Object(map) => map.get(key).ok_or_else(|| PathError::MissingKey {
+                path: self.rendered_path(),
+                key: key.into(),
+            })?,
+            other => {
+                return Err(PathError::NotAnObject {
+                    path: self.rendered_path(),
+                    found: type_name(other),
+                })
+            }
+        };
+        Ok(self.push(PathSegment::Key(key.into()), child))
+    }
+
+    /// Move to `index` of the current array, counting from the back if negative, staying put if it is out of bounds.
+    pub fn down_index(&mut self, index: isize) -> Result<&'a Value, PathError> {
+        let child = match self.current() {
+            Array(vec) => normalize_index(index, vec.len())
+                .map(|i| &vec[i])
+                .ok_or_else(|| PathError::OutOfBounds {
+                    path: self.rendered_path(),
+                    index,
+                })?,
+            other => {
+                return Err(PathError::NotAnArray {
+                    path: self.rendered_path(),
+                    found: type_name(other),
+                })
+            }
+        };
+        Ok(self.push(PathSegment::Index(index), child))
+    }
+
+    /// Move back to the parent, returning `None` if already at the root.
+    pub fn up(&mut self) -> Option<&'a Value> {
+        self.path.pop()?;
+        self.nodes.pop();
+        Some(self.current())
+    }
+
+    fn push(&mut self, segment: PathSegment, child: &'a Value) -> &'a Value {
+        self.path.push(segment);
+        self.nodes.push(child);
+        child
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn navigate() {
+        let data = json!({"a": {"b": {"c": [{"d": [0, [1, 2]]}, {"d": [3, 4]}]}}});
+        let mut cursor = Cursor::new(&data);
+        assert_eq!(cursor.rendered_path(), "@");
+        assert_eq!(cursor.up(), None);
+
+        cursor.down_key("a").unwrap();
+        cursor.down_key("b").unwrap();
+        cursor.down_key("c").unwrap();
+        assert_eq!(cursor.down_index(-1), Ok(&json!({"d": [3, 4]})));
+        assert_eq!(cursor.rendered_path(), "a.b.c[-1]");
+        assert_eq!(cursor.down_key("d").unwrap(), &json!([3, 4]));
+        assert_eq!(cursor.rendered_path(), "a.b.c[-1].d");
+
+        // Failures leave the cursor where it was
+        assert_eq!(
+            cursor.down_index(2),
+            Err(PathError::OutOfBounds {
+                path: "a.b.c[-1].d".into(),
+                index: 2
+            })
+        );
+        assert_eq!(cursor.current(), &json!([3, 4]));
+
+        assert_eq!(cursor.up(), Some(&json!({"d": [3, 4]})));
+        assert_eq!(cursor.up(), Some(&data["a"]["b"]["c"]));
+        cursor.down_index(0).unwrap();
+        assert_eq!(cursor.rendered_path(), "a.b.c[0]");
+        assert_eq!(
+            cursor.down_index(0),
+            Err(PathError::NotAnArray {
+                path: "a.b.c[0]".into(),
+                found: "object"
+            })
+        );
+        while cursor.up().is_some() {}
+        assert_eq!(cursor.current(), &data);
+    }
+}
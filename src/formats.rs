@@ -0,0 +1,101 @@
+//! Parse other formats into a [`Value`], so they can be queried like JSON.
+
+use serde_json::Value;
+#[cfg(feature = "toml")]
+use serde_json::Value::{Array, Object};
+
+/// Parse a YAML document.
+///
+/// Scalar mapping keys become strings, but sequence or mapping keys, and tagged values, are errors.
+#[cfg(feature = "yaml")]
+pub fn from_yaml(s: &str) -> Result<Value, serde_yaml::Error> {
+    serde_yaml::from_str(s)
+}
+
+/// Parse a TOML document.
+///
+/// Dates and times become their string representations.
+#[cfg(feature = "toml")]
+pub fn from_toml(s: &str) -> Result<Value, toml::de::Error> {
+    let table = s.parse::<toml::Table>()?;
+    let mut value = serde_json::to_value(table).expect("TOML tables have string keys");
+    unwrap_datetimes(&mut value);
+    Ok(value)
+}
+
+/// The `toml` crate serializes datetimes as an object with this single, private key.
+#[cfg(feature = "toml")]
+fn unwrap_datetimes(value: &mut Value) {
+    const DATETIME_KEY: &str = "$__toml_private_datetime";
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match value {
+            Object(map) if map.len() == 1 && map.contains_key(DATETIME_KEY) => {
+                *value = map.remove(DATETIME_KEY).expect("checked above")
+            }
+            Object(map) => stack.extend(map.values_mut()),
+            Array(vec) => stack.extend(vec),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml() {
+        use crate::{Expression, JMESPath};
+        let doc = from_yaml(
+            "
+servers:
+  - name: alpha
+    ports: [80, 443]
+  - name: beta
+    ports: [8080]
+",
+        )
+        .unwrap();
+        let path = "servers[0].ports[-1]"
+            .parse::<Expression>()
+            .unwrap()
+            .to_path()
+            .unwrap();
+        assert_eq!(doc.clone().get_path(&path), Ok(json!(443)));
+        assert_eq!(
+            doc.identify("servers")
+                .list_project(|server| server.identify("name")),
+            json!(["alpha", "beta"])
+        );
+        assert_eq!(from_yaml("{1: one}").unwrap(), json!({"1": "one"}));
+        assert!(from_yaml("{[1]: one}").is_err());
+        assert!(from_yaml("x: !tag 1").is_err());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml() {
+        let doc = from_toml(
+            r#"
+[package]
+name = "jmespath-native"
+released = 2021-12-20
+
+[[bin]]
+name = "jp"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            doc,
+            json!({
+                "package": {"name": "jmespath-native", "released": "2021-12-20"},
+                "bin": [{"name": "jp"}]
+            })
+        );
+        assert!(from_toml("name =").is_err());
+    }
+}
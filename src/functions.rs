@@ -0,0 +1,355 @@
+//! JMESPath's built-in functions, for [`Expression::FunctionCall`](crate::Expression::FunctionCall).
+//!
+//! Like the rest of the crate, arguments of the wrong type give `null`, rather than an error.
+
+use crate::{
+    check_sortable, compare_sortable, semantically_eq, sort_by_keys, type_name, Expression,
+    JMESPath, SearchError,
+};
+use serde_json::{
+    Map,
+    Value::{self, Array, Bool, Null, Number, Object},
+};
+use std::cmp::Ordering;
+
+/// An argument to a function.
+pub(crate) enum Argument<'a> {
+    Value(Value),
+    /// The expression of an [`Expression::ExprRef`], for the function to evaluate
+    Expression(&'a Expression),
+}
+
+/// Call the function `name` with `args`, using `evaluate` for their expression references.
+pub(crate) fn call(
+    name: &str,
+    args: Vec<Argument>,
+    evaluate: impl Fn(&Expression, Value) -> Result<Value, SearchError>,
+) -> Result<Value, SearchError> {
+    let (array, expression) = match name {
+        "sort_by" | "max_by" | "min_by" => match exactly(name, args)? {
+            [Argument::Value(Array(vec)), Argument::Expression(expression)] => (vec, expression),
+            _ => return Ok(Null),
+        },
+        "map" => match exactly(name, args)? {
+            [Argument::Expression(expression), Argument::Value(Array(vec))] => (vec, expression),
+            _ => return Ok(Null),
+        },
+        _ => {
+            // An expression reference isn't a value of any type
+            let args = args
+                .into_iter()
+                .map(|arg| match arg {
+                    Argument::Value(value) => value,
+                    Argument::Expression(_) => Null,
+                })
+                .collect();
+            return call_values(name, args);
+        }
+    };
+    let keys = array
+        .iter()
+        .map(|element| evaluate(expression, element.clone()))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(match name {
+        "sort_by" => sort_by_keys(array, keys, false),
+        "map" => Array(keys),
+        _ if check_sortable(&keys).is_err() => Null,
+        // `max_by` or `min_by`
+        _ => {
+            let better = match name {
+                "max_by" => Ordering::is_gt,
+                _ => Ordering::is_lt,
+            };
+            keys.iter()
+                .zip(array)
+                .reduce(
+                    |best, element| match better(compare_sortable(element.0, best.0)) {
+                        true => element,
+                        false => best,
+                    },
+                )
+                .map_or(Null, |(_key, element)| element)
+        }
+    })
+}
+
+/// Call a function which doesn't take expression references.
+fn call_values(name: &str, args: Vec<Value>) -> Result<Value, SearchError> {
+    Ok(match name {
+        "abs" => match exactly(name, args)? {
+            [Number(n)] => n
+                .as_i64()
+                .and_then(i64::checked_abs)
+                .map_or_else(|| Value::from(n.as_f64().map(f64::abs)), Value::from),
+            _ => Null,
+        },
+        "avg" => match exactly(name, args)? {
+            [Array(vec)] if !vec.is_empty() => match numbers(&vec) {
+                Some(numbers) => Value::from(numbers.iter().sum::<f64>() / numbers.len() as f64),
+                None => Null,
+            },
+            _ => Null,
+        },
+        "ceil" => match exactly(name, args)? {
+            [Number(n)] => integral(n.as_f64().map(f64::ceil)),
+            _ => Null,
+        },
+        "floor" => match exactly(name, args)? {
+            [Number(n)] => integral(n.as_f64().map(f64::floor)),
+            _ => Null,
+        },
+        "contains" => match exactly(name, args)? {
+            [Array(vec), search] => {
+                Bool(vec.iter().any(|element| semantically_eq(element, &search)))
+            }
+            [Value::String(s), Value::String(search)] => Bool(s.contains(&*search)),
+            [Value::String(_), _] => Bool(false),
+            _ => Null,
+        },
+        "ends_with" => match exactly(name, args)? {
+            [Value::String(s), Value::String(suffix)] => Bool(s.ends_with(&*suffix)),
+            _ => Null,
+        },
+        "starts_with" => match exactly(name, args)? {
+            [Value::String(s), Value::String(prefix)] => Bool(s.starts_with(&*prefix)),
+            _ => Null,
+        },
+        "join" => match exactly(name, args)? {
+            [Value::String(glue), Array(vec)] => vec
+                .iter()
+                .map(Value::as_str)
+                .collect::<Option<Vec<_>>>()
+                .map_or(Null, |strings| Value::String(strings.join(&glue))),
+            _ => Null,
+        },
+        "keys" => match exactly(name, args)? {
+            [Object(map)] => Array(map.into_iter().map(|(key, _)| Value::String(key)).collect()),
+            _ => Null,
+        },
+        "values" => match exactly(name, args)? {
+            [Object(map)] => Array(map.into_iter().map(|(_, value)| value).collect()),
+            _ => Null,
+        },
+        "length" => match exactly(name, args)? {
+            [Value::String(s)] => Value::from(s.chars().count()),
+            [Array(vec)] => Value::from(vec.len()),
+            [Object(map)] => Value::from(map.len()),
+            _ => Null,
+        },
+        "max" => match exactly(name, args)? {
+            [Array(vec)] => extreme(vec, |a, b| compare_sortable(a, b).is_gt()),
+            _ => Null,
+        },
+        "min" => match exactly(name, args)? {
+            [Array(vec)] => extreme(vec, |a, b| compare_sortable(a, b).is_lt()),
+            _ => Null,
+        },
+        "merge" => {
+            at_least_one(name, &args)?;
+            let mut merged = Map::new();
+            for arg in args {
+                match arg {
+                    Object(map) => merged.extend(map),
+                    _ => return Ok(Null),
+                }
+            }
+            Object(merged)
+        }
+        "not_null" => {
+            at_least_one(name, &args)?;
+            args.into_iter().find(|arg| !arg.is_null()).unwrap_or(Null)
+        }
+        "reverse" => match exactly(name, args)? {
+            [Value::String(s)] => Value::String(s.chars().rev().collect()),
+            [Array(mut vec)] => {
+                vec.reverse();
+                Array(vec)
+            }
+            _ => Null,
+        },
+        "sort" => {
+            let [value] = exactly(name, args)?;
+            value.sort()
+        }
+        "sum" => match exactly(name, args)? {
+            [Array(vec)] => sum(&vec),
+            _ => Null,
+        },
+        "to_array" => match exactly(name, args)? {
+            [Array(vec)] => Array(vec),
+            [value] => Array(vec![value]),
+        },
+        "to_number" => match exactly(name, args)? {
+            [Number(n)] => Number(n),
+            [Value::String(s)] => s.parse().map_or(Null, Number),
+            _ => Null,
+        },
+        "to_string" => match exactly(name, args)? {
+            [Value::String(s)] => Value::String(s),
+            [value] => Value::String(value.to_string()),
+        },
+        "type" => {
+            let [value] = exactly(name, args)?;
+            Value::from(type_name(&value))
+        }
+        _ => return Err(SearchError::UnknownFunction(name.into())),
+    })
+}
+
+fn exactly<const N: usize, T>(name: &str, args: Vec<T>) -> Result<[T; N], SearchError> {
+    let found = args.len();
+    args.try_into()
+        .map_err(|_| SearchError::WrongArgumentCount {
+            function: name.into(),
+            expected: N,
+            found,
+        })
+}
+
+/// For variadic functions
+fn at_least_one(name: &str, args: &[Value]) -> Result<(), SearchError> {
+    match args.is_empty() {
+        true => Err(SearchError::WrongArgumentCount {
+            function: name.into(),
+            expected: 1,
+            found: 0,
+        }),
+        false => Ok(()),
+    }
+}
+
+fn numbers(vec: &[Value]) -> Option<Vec<f64>> {
+    vec.iter().map(Value::as_f64).collect()
+}
+
+/// Integers stay integers, unless they overflow.
+fn sum(vec: &[Value]) -> Value {
+    let integers = vec.iter().map(Value::as_i64).collect::<Option<Vec<_>>>();
+    if let Some(sum) = integers.and_then(|integers| {
+        integers
+            .into_iter()
+            .try_fold(0i64, |sum, integer| sum.checked_add(integer))
+    }) {
+        return Value::from(sum);
+    }
+    numbers(vec).map_or(Null, |numbers| {
+        Value::from(numbers.into_iter().sum::<f64>())
+    })
+}
+
+/// The result of `ceil` or `floor`, as an integer if it fits.
+fn integral(f: Option<f64>) -> Value {
+    match f {
+        Some(f) if f.abs() < i64::MAX as f64 => Value::from(f as i64),
+        f => Value::from(f),
+    }
+}
+
+/// The first element of `vec` that no other is `better` than, or `null` if it is empty or unsortable.
+fn extreme(vec: Vec<Value>, better: impl Fn(&Value, &Value) -> bool) -> Value {
+    if check_sortable(&vec).is_err() {
+        return Null;
+    }
+    vec.into_iter()
+        .reduce(|best, element| match better(&element, &best) {
+            true => element,
+            false => best,
+        })
+        .unwrap_or(Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn call(name: &str, args: &[Value]) -> Value {
+        call_values(name, args.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(call("abs", &[json!(-2)]), json!(2));
+        assert_eq!(call("abs", &[json!(-2.5)]), json!(2.5));
+        assert_eq!(call("avg", &[json!([1, 2, 3, 4])]), json!(2.5));
+        assert_eq!(call("avg", &[json!([])]), Null);
+        assert_eq!(call("ceil", &[json!(1.2)]), json!(2));
+        assert_eq!(call("floor", &[json!(-1.2)]), json!(-2));
+        assert_eq!(call("sum", &[json!([1, 2, 3])]), json!(6));
+        assert_eq!(call("sum", &[json!([1, 2.5])]), json!(3.5));
+        assert_eq!(call("sum", &[json!([])]), json!(0));
+        assert_eq!(call("sum", &[json!([1, "2"])]), Null);
+        assert_eq!(call("max", &[json!([1, 3, 2])]), json!(3));
+        assert_eq!(call("min", &[json!(["b", "a"])]), json!("a"));
+        assert_eq!(call("max", &[json!([1, "a"])]), Null);
+        assert_eq!(call("max", &[json!([])]), Null);
+    }
+
+    #[test]
+    fn strings_and_collections() {
+        assert_eq!(call("length", &[json!("héllo")]), json!(5));
+        assert_eq!(call("length", &[json!({"a": 1})]), json!(1));
+        assert_eq!(call("length", &[json!(1)]), Null);
+        assert_eq!(call("contains", &[json!([1, 2.0]), json!(2)]), json!(true));
+        assert_eq!(
+            call("contains", &[json!("foobar"), json!("oba")]),
+            json!(true)
+        );
+        assert_eq!(
+            call("starts_with", &[json!("foobar"), json!("foo")]),
+            json!(true)
+        );
+        assert_eq!(
+            call("ends_with", &[json!("foobar"), json!("foo")]),
+            json!(false)
+        );
+        assert_eq!(
+            call("join", &[json!(", "), json!(["a", "b"])]),
+            json!("a, b")
+        );
+        assert_eq!(call("keys", &[json!({"a": 1, "b": 2})]), json!(["a", "b"]));
+        assert_eq!(call("values", &[json!({"a": 1, "b": 2})]), json!([1, 2]));
+        assert_eq!(
+            call("merge", &[json!({"a": 1, "b": 2}), json!({"b": 3})]),
+            json!({"a": 1, "b": 3})
+        );
+        assert_eq!(call("not_null", &[Null, json!([]), json!(1)]), json!([]));
+        assert_eq!(call("reverse", &[json!("abc")]), json!("cba"));
+        assert_eq!(call("sort", &[json!([3, 1, 2])]), json!([1, 2, 3]));
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(call("to_array", &[json!(1)]), json!([1]));
+        assert_eq!(call("to_array", &[json!([1])]), json!([1]));
+        assert_eq!(call("to_number", &[json!("1.5")]), json!(1.5));
+        assert_eq!(call("to_number", &[json!("one")]), Null);
+        assert_eq!(call("to_string", &[json!([1, "a"])]), json!(r#"[1,"a"]"#));
+        assert_eq!(call("to_string", &[json!("a")]), json!("a"));
+        assert_eq!(call("type", &[json!({})]), json!("object"));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            call_values("nope", vec![]),
+            Err(SearchError::UnknownFunction("nope".into()))
+        );
+        assert_eq!(
+            call_values("length", vec![json!(1), json!(2)]),
+            Err(SearchError::WrongArgumentCount {
+                function: "length".into(),
+                expected: 1,
+                found: 2
+            })
+        );
+        assert_eq!(
+            call_values("merge", vec![]),
+            Err(SearchError::WrongArgumentCount {
+                function: "merge".into(),
+                expected: 1,
+                found: 0
+            })
+        );
+    }
+}
@@ -1,8 +1,54 @@
-use serde_json::Value::{self, Array, Null, Object};
-use std::{num::NonZeroIsize, ops, str};
+use serde::de::DeserializeOwned;
+use serde_json::{
+    Map,
+    Value::{self, Array, Null, Object},
+};
+pub use slyce;
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt, mem,
+    num::NonZeroIsize,
+    ops, str,
+};
 use thiserror::Error;
+
 mod ast;
-#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg(feature = "jmespath-compat")]
+mod compat;
+mod cursor;
+#[cfg(any(feature = "yaml", feature = "toml"))]
+mod formats;
+mod functions;
+#[cfg(feature = "indexmap")]
+pub mod ordered;
+mod path;
+mod search;
+pub use ast::{Comparator, Expression, ParseExpressionError};
+#[cfg(feature = "jmespath-compat")]
+pub use compat::UnsupportedAst;
+pub use cursor::Cursor;
+#[cfg(feature = "toml")]
+pub use formats::from_toml;
+#[cfg(feature = "yaml")]
+pub use formats::from_yaml;
+/// Derive `fn from_value(value: Value) -> Option<Self>` for structs mirroring a document's shape.
+#[cfg(feature = "derive")]
+pub use jmespath_native_derive::JMESPath;
+pub use path::{render_path, PathError, PathSegment};
+pub use search::{
+    evaluate_prefix, search, search_many, search_to_writer, search_with, search_with_options,
+    trace, SearchError, SearchOptions,
+};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use serde;
+    pub use serde_json;
+}
+
+/// Ordered by `start`, then `end`, then `step`, where a missing part comes first.
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 pub struct JMESSlice {
     pub start: Option<isize>,
     pub end: Option<isize>,
@@ -17,6 +63,90 @@ pub enum ParseJMESSliceError {
     StepNotAllowedToBeZero,
 }
 
+/// Why [`JMESPath::try_sort`] couldn't sort.
+#[derive(Debug, Error, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum SortError {
+    #[error("Can only sort an array, not {0}")]
+    NotArray(&'static str),
+    #[error("Can only sort numbers or strings, not {0}")]
+    Unsortable(&'static str),
+    #[error("Expected all {expected}s, but found a {found} at index {index}")]
+    Heterogeneous {
+        index: usize,
+        expected: &'static str,
+        found: &'static str,
+    },
+}
+
+impl JMESSlice {
+    /// `::-1`
+    pub fn reversed() -> Self {
+        Self {
+            step: NonZeroIsize::new(-1),
+            ..Default::default()
+        }
+    }
+
+    /// This slice, stepping by `step`, like `JMESSlice::from(0..10).with_step(2)?` for `0:10:2`.
+    pub fn with_step(self, step: isize) -> Result<Self, ParseJMESSliceError> {
+        Ok(Self {
+            step: Some(NonZeroIsize::new(step).ok_or(ParseJMESSliceError::StepNotAllowedToBeZero)?),
+            ..self
+        })
+    }
+
+    /// `value.slice(self)`, for when a function is more convenient than a method.
+    pub fn apply_to(self, value: Value) -> Value {
+        value.slice(self)
+    }
+
+    /// The indices this selects from an array of length `len`, in order.
+    pub(crate) fn indices(self, len: usize) -> Vec<usize> {
+        let indices = (0..len).collect::<Vec<_>>();
+        self.to_slyce().apply(&indices).copied().collect()
+    }
+
+    fn to_slyce(self) -> slyce::Slice {
+        // Slicing makes my head hurt, use a library
+        slyce::Slice {
+            start: slyce_index(self.start),
+            end: slyce_index(self.end),
+            step: self.step.map(isize::from),
+        }
+    }
+}
+
+/// How a [`JMESSlice`] bound maps onto [`slyce`], which does the actual slicing.
+///
+/// Negative indices count from the end, and a missing bound is left for the step to decide.
+/// Unlike `slyce`'s own `From<isize>`, this doesn't overflow on [`isize::MIN`].
+pub fn slyce_index(index: Option<isize>) -> slyce::Index {
+    match index {
+        Some(i) if i.is_negative() => slyce::Index::Tail(i.unsigned_abs()),
+        Some(i) => slyce::Index::Head(i.unsigned_abs()),
+        None => slyce::Index::Default,
+    }
+}
+
+/// `start:end:step`, omitting what we can.
+impl fmt::Display for JMESSlice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(start) = self.start {
+            write!(f, "{}", start)?;
+        }
+        f.write_str(":")?;
+        if let Some(end) = self.end {
+            write!(f, "{}", end)?;
+        }
+        if let Some(step) = self.step {
+            write!(f, ":{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the contents of a slice expression, `start:end:step`, where every part is optional.
+/// The first colon is required, the second is not, so `:`, `::`, `1:`, `1::` and `:2:` are all valid.
 impl str::FromStr for JMESSlice {
     type Err = ParseJMESSliceError;
 
@@ -27,14 +157,15 @@ impl str::FromStr for JMESSlice {
             s
         )
         .ok_or(InvalidFormat)?;
+        // The regex ensures digits, but they may still overflow
         let option_isize = |s| match s {
-            "" => None,
-            s => Some(s.parse::<isize>().expect("Regex ensures valid")),
+            "" => Ok(None),
+            s => s.parse::<isize>().map(Some).map_err(|_| InvalidFormat),
         };
         let ok = Self {
-            start: option_isize(start),
-            end: option_isize(end),
-            step: match option_isize(step) {
+            start: option_isize(start)?,
+            end: option_isize(end)?,
+            step: match option_isize(step)? {
                 Some(i) => Some(NonZeroIsize::new(i).ok_or(StepNotAllowedToBeZero)?),
                 None => None,
             },
@@ -43,6 +174,62 @@ impl str::FromStr for JMESSlice {
     }
 }
 
+/// Conversions from other integer ranges, via the `isize` ones.
+/// Bounds past [`isize::MAX`] saturate, which selects the same elements, as no array is that long.
+///
+/// With only `isize` and `usize`, unsuffixed literals like `1..3` would be ambiguous and fall back to `i32`,
+/// so implement that too.
+macro_rules! from_ranges {
+    ($($ty:ty),*) => {$(
+        impl From<ops::Range<$ty>> for JMESSlice {
+            fn from(range: ops::Range<$ty>) -> Self {
+                (saturating_isize(range.start)..saturating_isize(range.end)).into()
+            }
+        }
+        impl From<ops::RangeFrom<$ty>> for JMESSlice {
+            fn from(range: ops::RangeFrom<$ty>) -> Self {
+                (saturating_isize(range.start)..).into()
+            }
+        }
+        impl From<ops::RangeTo<$ty>> for JMESSlice {
+            fn from(range: ops::RangeTo<$ty>) -> Self {
+                (..saturating_isize(range.end)).into()
+            }
+        }
+    )*};
+}
+from_ranges!(usize, i32);
+
+fn saturating_isize(i: impl TryInto<isize>) -> isize {
+    i.try_into().unwrap_or(isize::MAX)
+}
+
+/// From an object with optional integer `start`, `end` and `step` fields.
+impl TryFrom<&Value> for JMESSlice {
+    type Error = ParseJMESSliceError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        use ParseJMESSliceError::{InvalidFormat, StepNotAllowedToBeZero};
+        let map = value.as_object().ok_or(InvalidFormat)?;
+        let option_isize = |key| match map.get(key) {
+            None | Some(Null) => Ok(None),
+            Some(value) => value
+                .as_i64()
+                .and_then(|i| isize::try_from(i).ok())
+                .map(Some)
+                .ok_or(InvalidFormat),
+        };
+        Ok(Self {
+            start: option_isize("start")?,
+            end: option_isize("end")?,
+            step: match option_isize("step")? {
+                Some(i) => Some(NonZeroIsize::new(i).ok_or(StepNotAllowedToBeZero)?),
+                None => None,
+            },
+        })
+    }
+}
+
 impl From<ops::Range<isize>> for JMESSlice {
     fn from(range: ops::Range<isize>) -> Self {
         Self {
@@ -69,67 +256,677 @@ impl From<ops::RangeTo<isize>> for JMESSlice {
     }
 }
 
+/// What [`JMESPath::index_with`] does with an out-of-bounds index.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum OobPolicy {
+    /// Return `null`, like [`JMESPath::index`].
+    #[default]
+    Null,
+    /// Return the first or last element.
+    Clamp,
+    /// Index modulo the length of the array.
+    Wrap,
+}
+
+/// An entry in a [`JMESPath::multiselect_hash`].
+pub enum HashEntry<'a, T = Value> {
+    /// `key: expression`
+    Named(&'a str, &'a dyn Fn(T) -> T),
+    /// `..expression`, merging the resulting object's entries in, like a JavaScript spread.
+    /// Non-objects contribute nothing.
+    Spread(&'a dyn Fn(T) -> T),
+}
+
 pub trait JMESPath: Sized {
+    /// The value at `key`, or `null`. With the `strict-debug` feature, panics if `self` isn't an object.
     fn identify(self, key: impl AsRef<str>) -> Self;
+    /// The value at `key` if it is present and not `null`, else `default`.
+    /// Unlike `||`, other falsey values such as `false` or `""` are returned as-is.
+    fn identify_or(self, key: impl AsRef<str>, default: Self) -> Self;
+    /// The values of an object whose keys start with `prefix`, in map order.
+    /// This is always an array, even if only one key matches.
+    fn identify_prefix(self, prefix: impl AsRef<str>) -> Self;
+    /// Follow an RFC 6901 JSON Pointer like `/a/b/0`, where `~1` is a `/` in a key and `~0` a `~`.
+    /// `""` is the whole document, and anything which doesn't resolve is `null`.
+    fn pointer(self, pointer: &str) -> Self;
+    /// [`JMESPath::identify`], projecting over arrays the way `people.first` does when `people` is an array.
+    fn identify_projecting(self, key: impl AsRef<str>) -> Self;
+    /// [`JMESPath::identify`], but failing if `self` isn't an object, or doesn't have `key`.
+    fn try_identify(self, key: impl AsRef<str>) -> Result<Self, PathError>;
+    /// Follow `path`, failing with where and why it couldn't be followed.
+    /// Slices project the rest of the path, like [`JMESPath::slice_project`].
+    fn get_path(self, path: &[PathSegment]) -> Result<Self, PathError>;
+    /// Replace the node at `path` with `value`, if there is one.
+    /// Nothing is created along the way, so if `path` doesn't exist the document is returned unchanged.
+    fn replace_at_path(self, path: &[PathSegment], value: Self) -> Self;
+    /// Move the node at `path` out of the document, removing its key from an object,
+    /// or leaving `null` in an array so later elements keep their indices.
+    /// `null` if there is no such node, which there never is through a slice.
+    fn take_path(&mut self, path: &[PathSegment]) -> Self;
+    /// Check that each of `paths` leads to something other than `null`,
+    /// else fail with each of those which don't, rendered.
+    fn require_paths(&self, paths: &[&[PathSegment]]) -> Result<(), Vec<String>>;
+    /// Whether an object has `key`, even if its value is `null`.
+    fn contains_key(self, key: impl AsRef<str>) -> Self;
+    /// The element at `index`, or `null`. With the `strict-debug` feature, panics if `self` isn't an array.
     fn index(self, index: isize) -> Self;
+    /// [`JMESPath::index`], with `oob` choosing what an out-of-bounds index does: `null`, clamp, or wrap.
+    /// `null` for anything but an array, or an empty one, as there is nothing to clamp or wrap to.
+    fn index_with(self, index: isize, oob: OobPolicy) -> Self;
+    /// Slice an array as Python does. A step larger than the array still takes the element it starts on,
+    /// so `"::100"` is the first element, and `"::-100"` the last.
     fn slice(self, slice: impl Into<JMESSlice>) -> Self;
+    /// [`JMESPath::slice`], but including the element at `end`, so `(Some(0), Some(2), None)` takes three.
+    /// `slice` itself always excludes `end`. `null` if `step` is zero.
+    fn slice_inclusive(self, start: Option<isize>, end: Option<isize>, step: Option<isize>)
+        -> Self;
+    /// [`JMESPath::slice`] with a slice like `"::2"`, or `null` if it doesn't parse.
+    fn slice_str_expr(self, slice: &str) -> Self;
+    /// [`JMESPath::slice`], with each element as `[index, value]`, where `index` is its position in `self`.
+    fn slice_enumerated(self, slice: impl Into<JMESSlice>) -> Self;
+    /// Slice a string's UTF-8 bytes, rather than its characters.
+    /// The result must still be valid UTF-8, so slicing through a multi-byte character, or with a negative step
+    /// over one, gives `null`.
+    fn slice_bytes(self, slice: impl Into<JMESSlice>) -> Self;
     fn list_project(self, projection: impl Fn(Self) -> Self) -> Self;
+    /// [`JMESPath::list_project`], also passing each element's index in the array.
+    fn list_project_indexed(self, projection: impl Fn(usize, Self) -> Self) -> Self;
+    /// [`JMESPath::list_project`], but keeping `null` results, so each is at the same index as its element.
+    /// This is what JMESPath's `map` function does, where a projection would drop them.
+    fn list_project_keep_nulls(self, projection: impl Fn(Self) -> Self) -> Self;
     fn slice_project(self, slice: impl Into<JMESSlice>, projection: impl Fn(Self) -> Self) -> Self;
     fn object_project(self, projection: impl Fn(Self) -> Self) -> Self;
+    /// Fill in keys from `defaults` which are missing, recursing into objects present in both.
+    /// Existing keys are never overwritten, and anything but an object is returned as-is.
+    fn with_defaults(self, defaults: &Self) -> Self;
+    /// Keep only the parts of the document selected by `mask`, like a GraphQL selection.
+    /// Where `mask` is `true`, the whole subtree is kept, and where it is an object, only its keys are,
+    /// each masked in turn. Arrays have the mask applied to each element.
+    /// Anything else the mask doesn't describe, like a string where it expects an object, is `null`.
+    fn project_mask(self, mask: &Self) -> Self;
+    /// Replace each of an object's values with `f(value)`, keeping its keys.
+    /// Unlike [`JMESPath::object_project`], `null` results are kept.
+    fn map_values(self, f: impl Fn(Self) -> Self) -> Self;
+    /// Replace each of an object's keys with `f(key)`, where later keys in map order win on collision.
+    fn map_keys(self, f: impl Fn(&str) -> String) -> Self;
     #[deprecated]
     fn flatten(self) -> Self;
     fn flatten_project(self, projection: impl Fn(Self) -> Self) -> Self;
+    /// The same as [`JMESPath::flatten`], but reusing the outer array's allocation.
+    fn flatten_in_place(&mut self);
+    /// [`JMESPath::flatten`] `depth` times, so `0` leaves the array as it is.
+    fn flatten_n(self, depth: usize) -> Self;
+    /// All scalar values in the document, depth-first, ignoring keys.
+    fn leaves(self) -> Self;
+    /// The path to each of [`JMESPath::leaves`], in the same order.
+    fn paths(&self) -> impl Iterator<Item = Vec<PathSegment>> + '_;
+    /// Truncate every string value in the document to at most `max_chars` characters,
+    /// with the last being `…` if `ellipsis` and the string was truncated.
+    /// Object keys are left alone.
+    fn truncate_strings(self, max_chars: usize, ellipsis: bool) -> Self;
+    /// Rewrite every float with an integer value, like `2.0`, as that integer, if it fits in an `i64` or `u64`.
+    /// Nothing else changes, so every number still has the same value.
+    fn normalize_numbers(self) -> Self;
+    /// Replace the value at any of `keys`, anywhere in the document, with `replacement`.
+    /// Keys must match whole, but case-insensitively, so `"password"` also redacts `"Password"`.
+    fn redact(self, keys: &[&str], replacement: Value) -> Self;
+    /// Sort an array of numbers, or an array of strings.
+    /// Integers and floats are all numbers, so may be mixed.
+    fn sort(self) -> Self;
+    /// [`JMESPath::sort`], but explaining why a value couldn't be sorted rather than returning `null`.
+    fn try_sort(self) -> Result<Self, SortError>;
+    /// Sort an array by `key` of each element, which must be all numbers or all strings.
+    /// The sort is stable.
+    fn sort_by(self, key: impl Fn(&Self) -> Self) -> Self;
+    /// [`JMESPath::sort_by`], largest first. Ties still keep their order.
+    fn sort_by_desc(self, key: impl Fn(&Self) -> Self) -> Self;
+    /// The numbers in an array with `lo <= x <= hi`, in their original order.
+    /// `null` unless `lo` and `hi` are numbers.
+    fn between(self, lo: &Self, hi: &Self) -> Self;
+    /// Slice an object's values, in map order.
+    fn slice_values(self, slice: impl Into<JMESSlice>) -> Self;
+    /// [`JMESPath::list_project`], then deserialize each result.
+    /// Fails with the first result which doesn't deserialize, naming the index of its element in the array,
+    /// or if `self` isn't an array.
+    fn project_typed<T: DeserializeOwned>(
+        self,
+        projection: impl Fn(Self) -> Self,
+    ) -> Result<Vec<T>, serde_json::Error>;
+    /// Build an object from `entries`, where later entries override earlier ones.
+    fn multiselect_hash(self, entries: &[HashEntry<Self>]) -> Self;
+    /// Group an array's elements into an object of arrays by `key`, which must be a string for every element.
+    fn group_by(self, key: impl Fn(&Self) -> Self) -> Self;
+    /// Overlapping sub-arrays of length `size`, sliding by one.
+    fn windows(self, size: usize) -> Self;
+    /// The first `n` distinct elements of an array, in order, where elements are distinct if not [`semantically_eq`].
+    /// Stops looking once it has `n`, so the rest of the array is never compared.
+    fn sample(self, n: usize) -> Self;
+    /// Rotate an array left by `by`, or right if it is negative, wrapping around as many times as needed.
+    fn rotate(self, by: isize) -> Self;
+    /// Whether any element of an array satisfies `predicate`. `false` for an empty array.
+    fn any(self, predicate: impl Fn(&Self) -> bool) -> Self;
+    /// Whether all elements of an array satisfy `predicate`. `true` for an empty array.
+    fn all(self, predicate: impl Fn(&Self) -> bool) -> Self;
+    /// The first key of an object whose value satisfies `predicate`, in map order, or `null` if there is none.
+    fn find_key_by_value(self, predicate: impl Fn(&Self) -> bool) -> Self;
+    /// `[*].key`
+    fn pluck(self, key: impl AsRef<str>) -> Self;
+    /// Each element of an array as a row of its values at `columns`, in that order, with `null` for those missing.
+    /// Unlike a projection, every element has a row, even if it is all `null`.
+    fn to_records(self, columns: &[&str]) -> Self;
+    /// Rename an object's keys which are in `mapping`.
+    /// If keys collide, the last in map order wins.
+    fn rename_keys(self, mapping: &HashMap<String, String>) -> Self;
+    /// An object with only the given keys, where present.
+    fn pick(self, keys: &[&str]) -> Self;
+    /// An object without the given keys.
+    fn omit(self, keys: &[&str]) -> Self;
+    /// An object used as a sparse array, like `{"0": "a", "2": "c"}`, as a dense one, like `["a", null, "c"]`.
+    /// Keys must all be non-negative integers without leading zeros, and the array is as long as the largest plus one,
+    /// which may be at most `max_len`, so a stray large key can't allocate a huge array of `null`s.
+    // Consuming, like every other conversion here
+    #[allow(clippy::wrong_self_convention)]
+    fn as_sparse_array(self, max_len: usize) -> Self;
+    /// The elements of an array, or `None` for anything else.
+    fn into_array(self) -> Option<Vec<Self>>;
+    /// The entries of an object, or `None` for anything else.
+    fn into_object(self) -> Option<Map<String, Value>>;
+}
+
+/// Resolve `index` into an array of length `len`, counting from the back if negative.
+/// `None` if out of bounds.
+fn normalize_index(index: isize, len: usize) -> Option<usize> {
+    let index = if index.is_negative() {
+        // Get the index from the back
+        len.checked_sub(index.unsigned_abs())?
+    } else {
+        index.unsigned_abs()
+    };
+    (index < len).then_some(index)
+}
+
+// These are generic over the map they build, so `ordered` can share them.
+// Later entries override earlier ones, which both `Map` and `IndexMap` do when collecting.
+
+/// A hash of `value` which is the same across runs and platforms, and agrees with [`semantically_eq`],
+/// so `1` and `1.0` hash the same, as do objects with their keys in different orders.
+pub fn structural_hash(value: &Value) -> u64 {
+    enum Item<'a> {
+        Value(&'a Value),
+        Key(&'a str),
+    }
+    // FNV-1a, which is simple and unseeded
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    let mut write = |bytes: &[u8]| {
+        for byte in bytes {
+            hash = (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        }
+    };
+    let mut stack = vec![Item::Value(value)];
+    while let Some(item) = stack.pop() {
+        // Each is tagged, and prefixed with its length, so different structures don't run together
+        let value = match item {
+            Item::Key(key) => {
+                write(&(key.len() as u64).to_le_bytes());
+                write(key.as_bytes());
+                continue;
+            }
+            Item::Value(value) => value,
+        };
+        match value {
+            Null => write(&[0]),
+            Value::Bool(b) => write(&[1, u8::from(*b)]),
+            // Numbers which compare equal are the same as floats, which is all that
+            // `compare_numbers` can tell apart between integers and floats. Adding zero turns `-0` into `0`.
+            Value::Number(n) => {
+                write(&[2]);
+                write(
+                    &(n.as_f64().expect("JSON numbers are finite") + 0.0)
+                        .to_bits()
+                        .to_le_bytes(),
+                )
+            }
+            Value::String(s) => {
+                write(&[3]);
+                write(&(s.len() as u64).to_le_bytes());
+                write(s.as_bytes())
+            }
+            Array(vec) => {
+                write(&[4]);
+                write(&(vec.len() as u64).to_le_bytes());
+                stack.extend(vec.iter().rev().map(Item::Value))
+            }
+            Object(map) => {
+                write(&[5]);
+                write(&(map.len() as u64).to_le_bytes());
+                // In case of serde_json's `preserve_order`
+                let mut entries = map.iter().collect::<Vec<_>>();
+                entries.sort_by_key(|(key, _)| *key);
+                for (key, value) in entries.into_iter().rev() {
+                    stack.extend([Item::Value(value), Item::Key(key)])
+                }
+            }
+        }
+    }
+    hash
+}
+
+/// See [`JMESPath::multiselect_hash`].
+fn multiselect_hash<M: FromIterator<(String, Value)>>(
+    value: Value,
+    entries: &[HashEntry],
+) -> Option<M> {
+    if value.is_null() {
+        return None;
+    }
+    let mut pairs = Vec::new();
+    for entry in entries {
+        match entry {
+            HashEntry::Named(key, expression) => {
+                pairs.push((key.to_string(), expression(value.clone())))
+            }
+            HashEntry::Spread(expression) => {
+                if let Object(spread) = expression(value.clone()) {
+                    pairs.extend(spread)
+                }
+            }
+        }
+    }
+    Some(pairs.into_iter().collect())
+}
+
+/// See [`JMESPath::group_by`]. Groups are in the order their keys are first seen.
+fn group_by<M: FromIterator<(String, Value)>>(
+    value: Value,
+    key: impl Fn(&Value) -> Value,
+) -> Option<M> {
+    let Array(vec) = value else {
+        return None;
+    };
+    let mut groups = Vec::<(String, Vec<Value>)>::new();
+    let mut indices = HashMap::new();
+    for element in vec {
+        let Value::String(key) = key(&element) else {
+            return None;
+        };
+        let index = *indices.entry(key.clone()).or_insert_with(|| {
+            groups.push((key, Vec::new()));
+            groups.len() - 1
+        });
+        groups[index].1.push(element)
+    }
+    Some(
+        groups
+            .into_iter()
+            .map(|(key, group)| (key, Array(group)))
+            .collect(),
+    )
+}
+
+/// See [`JMESPath::map_keys`].
+fn map_keys<M: FromIterator<(String, Value)>>(
+    value: Value,
+    f: impl Fn(&str) -> String,
+) -> Option<M> {
+    let Object(map) = value else {
+        return None;
+    };
+    Some(
+        map.into_iter()
+            .map(|(key, value)| (f(&key), value))
+            .collect(),
+    )
+}
+
+/// Whether `vec` is all numbers or all strings, so can be sorted with [`compare_sortable`].
+fn check_sortable(vec: &[Value]) -> Result<(), SortError> {
+    let expected = match vec.first() {
+        None => return Ok(()),
+        Some(first @ (Value::Number(_) | Value::String(_))) => type_name(first),
+        Some(other) => return Err(SortError::Unsortable(type_name(other))),
+    };
+    match vec
+        .iter()
+        .enumerate()
+        .find(|(_, value)| type_name(value) != expected)
+    {
+        Some((index, found)) => Err(SortError::Heterogeneous {
+            index,
+            expected,
+            found: type_name(found),
+        }),
+        None => Ok(()),
+    }
+}
+
+fn compare_sortable(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => compare_numbers(a, b),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        _ => unreachable!("Checked homogeneous"),
+    }
+}
+
+/// See [`JMESPath::sort_by`].
+fn sort_by_key(value: Value, key: impl Fn(&Value) -> Value, descending: bool) -> Value {
+    let vec = match value {
+        Array(vec) => vec,
+        _ => return Null,
+    };
+    let keys = vec.iter().map(key).collect();
+    sort_by_keys(vec, keys, descending)
+}
+
+/// Sort `vec` by the corresponding `keys`, or `null` if they can't be sorted.
+fn sort_by_keys(vec: Vec<Value>, keys: Vec<Value>, descending: bool) -> Value {
+    if check_sortable(&keys).is_err() {
+        return Null;
+    }
+    let mut keyed = keys.into_iter().zip(vec).collect::<Vec<_>>();
+    // Both stable, so ties keep their order either way
+    match descending {
+        true => keyed.sort_by(|(a, _), (b, _)| compare_sortable(b, a)),
+        false => keyed.sort_by(|(a, _), (b, _)| compare_sortable(a, b)),
+    }
+    Array(keyed.into_iter().map(|(_key, value)| value).collect())
+}
+
+/// The name of `value`'s type, as JMESPath's `type` function would return.
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Array(_) => "array",
+        Object(_) => "object",
+    }
+}
+
+/// Compare JSON numbers by value, whatever their representation.
+/// JSON has no NaN, so this is a total order.
+fn compare_numbers(a: &serde_json::Number, b: &serde_json::Number) -> Ordering {
+    // Large integers would lose precision as floats, and could compare equal
+    let integer = |n: &serde_json::Number| {
+        n.as_i64()
+            .map(i128::from)
+            .or_else(|| n.as_u64().map(i128::from))
+    };
+    match (integer(a), integer(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        _ => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .expect("JSON numbers are never NaN"),
+    }
+}
+
+/// Structural equality, except numbers are compared by value, so `1` and `1.0` are equal.
+///
+/// This is the equality for everything which compares values, like `==` in filters and [`diff`].
+pub fn semantically_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => compare_numbers(a, b).is_eq(),
+        (Array(a), Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| semantically_eq(a, b))
+        }
+        (Object(a), Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| semantically_eq(a, b)))
+        }
+        _ => a == b,
+    }
+}
+
+/// [`semantically_eq`], once everything at `ignore_paths` is removed from both `a` and `b`,
+/// like volatile timestamps or ids in a snapshot.
+///
+/// A path through a slice ignores the rest of it in each element the slice selects,
+/// so `items[:].id` ignores every item's `id`.
+pub fn eq_ignoring(a: &Value, b: &Value, ignore_paths: &[&[PathSegment]]) -> bool {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    for path in ignore_paths {
+        path::remove_all(&mut a, path);
+        path::remove_all(&mut b, path);
+    }
+    semantically_eq(&a, &b)
+}
+
+/// The value at `key`, or `null`, even with `strict-debug`.
+pub(crate) fn lenient_identify(value: Value, key: &str) -> Value {
+    match value {
+        Object(mut map) => map.remove(key).unwrap_or(Null),
+        _ => Null,
+    }
+}
+
+/// The element at `index`, or `null`, even with `strict-debug`.
+pub(crate) fn lenient_index(value: Value, index: isize) -> Value {
+    match value {
+        Array(mut vec) => match normalize_index(index, vec.len()) {
+            Some(index) => vec.swap_remove(index),
+            None => Null, // OOB
+        },
+        _ => Null,
+    }
+}
+
+/// `null`, for `value` not being `expected`, or a panic saying so with the `strict-debug` feature,
+/// to catch queries which don't fit the data they're run against.
+#[cfg_attr(feature = "strict-debug", track_caller)]
+fn mismatched(expected: &str, operation: fmt::Arguments, value: &Value) -> Value {
+    if cfg!(feature = "strict-debug") {
+        panic!(
+            "Expected {expected} to {operation}, but found {}: {value}",
+            type_name(value)
+        )
+    }
+    Null
 }
 
 impl JMESPath for Value {
+    #[cfg_attr(feature = "strict-debug", track_caller)]
     fn identify(self, key: impl AsRef<str>) -> Self {
         match self {
-            Object(mut map) => map.remove(key.as_ref()).unwrap_or(Null),
+            Object(_) => lenient_identify(self, key.as_ref()),
+            other => mismatched(
+                "an object",
+                format_args!("identify {:?}", key.as_ref()),
+                &other,
+            ),
+        }
+    }
+
+    fn identify_or(self, key: impl AsRef<str>, default: Self) -> Self {
+        match lenient_identify(self, key.as_ref()) {
+            Null => default,
+            value => value,
+        }
+    }
+
+    fn identify_projecting(self, key: impl AsRef<str>) -> Self {
+        match self {
+            Array(_) => self.list_project(|value| lenient_identify(value, key.as_ref())),
+            other => lenient_identify(other, key.as_ref()),
+        }
+    }
+
+    fn try_identify(self, key: impl AsRef<str>) -> Result<Self, PathError> {
+        self.get_path(&[PathSegment::Key(key.as_ref().into())])
+    }
+
+    fn get_path(self, path: &[PathSegment]) -> Result<Self, PathError> {
+        path::get_path(self, path)
+    }
+
+    fn replace_at_path(mut self, path: &[PathSegment], value: Self) -> Self {
+        if let Some(node) = path::lookup_mut(&mut self, path) {
+            *node = value
+        }
+        self
+    }
+
+    fn take_path(&mut self, path: &[PathSegment]) -> Self {
+        let Some((last, parent)) = path.split_last() else {
+            return mem::take(self);
+        };
+        match (last, path::lookup_mut(self, parent)) {
+            (PathSegment::Key(key), Some(Object(map))) => map.remove(key).unwrap_or(Null),
+            (PathSegment::Index(index), Some(Array(vec))) => {
+                match normalize_index(*index, vec.len()) {
+                    Some(index) => mem::take(&mut vec[index]),
+                    None => Null,
+                }
+            }
             _ => Null,
         }
     }
 
-    fn index(self, index: isize) -> Self {
+    fn require_paths(&self, paths: &[&[PathSegment]]) -> Result<(), Vec<String>> {
+        let missing = paths
+            .iter()
+            .filter(|path| path::lookup(self, path).is_none_or(|value| value.is_null()))
+            .map(|path| render_path(path))
+            .collect::<Vec<_>>();
+        match missing.is_empty() {
+            true => Ok(()),
+            false => Err(missing),
+        }
+    }
+
+    fn identify_prefix(self, prefix: impl AsRef<str>) -> Self {
         match self {
-            Array(mut vec) => {
-                let index = if index.is_negative() {
-                    // Get the index from the back
-                    match vec.len().checked_sub(index.unsigned_abs()) {
-                        Some(u) => u,
-                        None => return Null,
+            Object(map) => Array(
+                map.into_iter()
+                    .filter(|(key, _)| key.starts_with(prefix.as_ref()))
+                    .map(|(_key, value)| value)
+                    .collect(),
+            ),
+            _ => Null,
+        }
+    }
+
+    fn pointer(self, pointer: &str) -> Self {
+        if pointer.is_empty() {
+            return self;
+        }
+        let Some(pointer) = pointer.strip_prefix('/') else {
+            return Null;
+        };
+        let mut value = self;
+        for token in pointer.split('/') {
+            // In this order, so `~01` is `~1`
+            let token = token.replace("~1", "/").replace("~0", "~");
+            value = match value {
+                Object(_) => lenient_identify(value, &token),
+                // Only canonical, non-negative indices, so not `01` or `-1`
+                Array(_) => match token.parse::<isize>() {
+                    Ok(index) if index >= 0 && token == index.to_string() => {
+                        lenient_index(value, index)
                     }
-                } else {
-                    index.unsigned_abs()
+                    _ => Null,
+                },
+                _ => Null,
+            };
+        }
+        value
+    }
+
+    fn contains_key(self, key: impl AsRef<str>) -> Self {
+        match self {
+            Object(map) => Value::Bool(map.contains_key(key.as_ref())),
+            _ => Null,
+        }
+    }
+
+    #[cfg_attr(feature = "strict-debug", track_caller)]
+    fn index(self, index: isize) -> Self {
+        match self {
+            Array(_) => lenient_index(self, index),
+            other => mismatched("an array", format_args!("index [{index}]"), &other),
+        }
+    }
+
+    fn index_with(self, index: isize, oob: OobPolicy) -> Self {
+        match self {
+            Array(vec) if vec.is_empty() => Null, // Nothing to clamp or wrap to
+            Array(vec) => {
+                let len = vec.len() as isize;
+                let index = match oob {
+                    OobPolicy::Null => index,
+                    OobPolicy::Clamp => index.clamp(-len, len - 1),
+                    OobPolicy::Wrap => index.rem_euclid(len),
                 };
-                if index < vec.len() {
-                    vec.remove(index)
-                } else {
-                    Null // OOB
-                }
+                lenient_index(Array(vec), index)
             }
             _ => Null,
         }
     }
 
     fn slice(self, slice: impl Into<JMESSlice>) -> Self {
-        use slyce::{Index, Slice}; // Slicing makes my head hurt, use a library
         let slice: JMESSlice = slice.into();
         match self {
-            Array(vec) => {
-                let op = Slice {
-                    start: match slice.start {
-                        Some(i) if i.is_negative() => Index::Tail(i.unsigned_abs()),
-                        Some(i) => Index::Head(i.unsigned_abs()),
-                        None => Index::Default,
-                    },
-                    end: match slice.end {
-                        Some(i) if i.is_negative() => Index::Tail(i.unsigned_abs()),
-                        Some(i) => Index::Head(i.unsigned_abs()),
-                        None => Index::Default,
-                    },
-                    step: slice.step.map(isize::from),
-                };
-                Array(op.apply(&vec).map(Clone::clone).collect())
+            // Common enough to be worth moving rather than cloning
+            Array(mut vec) if slice == JMESSlice::reversed() => {
+                vec.reverse();
+                Array(vec)
+            }
+            Array(vec) => Array(slice.to_slyce().apply(&vec).map(Clone::clone).collect()),
+            _ => Null,
+        }
+    }
+
+    fn slice_inclusive(
+        self,
+        start: Option<isize>,
+        end: Option<isize>,
+        step: Option<isize>,
+    ) -> Self {
+        let Ok(step) = step.map(NonZeroIsize::try_from).transpose() else {
+            return Null;
+        };
+        // One further in the direction of travel, unless that wraps around to the other end,
+        // in which case leaving the end out reaches it anyway
+        let end = match step.map_or(1, isize::from) > 0 {
+            true => end
+                .and_then(|end| end.checked_add(1))
+                .filter(|end| *end != 0),
+            false => end
+                .and_then(|end| end.checked_sub(1))
+                .filter(|end| *end != -1),
+        };
+        self.slice(JMESSlice { start, end, step })
+    }
+
+    fn slice_str_expr(self, slice: &str) -> Self {
+        match slice.parse::<JMESSlice>() {
+            Ok(slice) => self.slice(slice),
+            Err(_) => Null,
+        }
+    }
+
+    fn slice_enumerated(self, slice: impl Into<JMESSlice>) -> Self {
+        let slice: JMESSlice = slice.into();
+        match self {
+            Array(mut vec) => Array(
+                slice
+                    .indices(vec.len())
+                    .into_iter()
+                    // Slices never repeat an index, so we can move rather than clone
+                    .map(|i| Array(vec![i.into(), mem::take(&mut vec[i])]))
+                    .collect(),
+            ),
+            _ => Null,
+        }
+    }
+
+    fn slice_bytes(self, slice: impl Into<JMESSlice>) -> Self {
+        let slice: JMESSlice = slice.into();
+        match self {
+            Value::String(s) => {
+                let bytes = slice.to_slyce().apply(s.as_bytes()).copied().collect();
+                String::from_utf8(bytes).map_or(Null, Value::String)
             }
             _ => Null,
         }
@@ -147,6 +944,26 @@ impl JMESPath for Value {
         }
     }
 
+    fn list_project_indexed(self, projection: impl Fn(usize, Self) -> Self) -> Self {
+        match self {
+            Array(vec) => Array(
+                vec.into_iter()
+                    .enumerate()
+                    .map(|(i, value)| projection(i, value))
+                    .filter(|value| !value.is_null())
+                    .collect(),
+            ),
+            _ => Null,
+        }
+    }
+
+    fn list_project_keep_nulls(self, projection: impl Fn(Self) -> Self) -> Self {
+        match self {
+            Array(vec) => Array(vec.into_iter().map(projection).collect()),
+            _ => Null,
+        }
+    }
+
     fn slice_project(self, slice: impl Into<JMESSlice>, projection: impl Fn(Self) -> Self) -> Self {
         match self {
             Array(_) => self.slice(slice).list_project(projection),
@@ -167,6 +984,57 @@ impl JMESPath for Value {
         }
     }
 
+    fn with_defaults(self, defaults: &Self) -> Self {
+        match (self, defaults) {
+            (Object(mut map), Object(defaults)) => {
+                for (key, default) in defaults {
+                    match map.get_mut(key) {
+                        Some(value) => *value = mem::take(value).with_defaults(default),
+                        None => {
+                            map.insert(key.clone(), default.clone());
+                        }
+                    }
+                }
+                Object(map)
+            }
+            (other, _) => other,
+        }
+    }
+
+    fn project_mask(mut self, mask: &Self) -> Self {
+        let selects = |mask: &Value| matches!(mask, Value::Bool(true) | Object(_));
+        let mut stack = vec![(&mut self, mask)];
+        while let Some((value, mask)) = stack.pop() {
+            match (value, mask) {
+                (_, Value::Bool(true)) => (),
+                (Object(map), Object(mask)) => {
+                    map.retain(|key, _| mask.get(key).is_some_and(selects));
+                    stack.extend(map.iter_mut().map(|(key, value)| (value, &mask[key])))
+                }
+                (Array(vec), mask @ Object(_)) => {
+                    stack.extend(vec.iter_mut().map(|value| (value, mask)))
+                }
+                (value, _) => *value = Null,
+            }
+        }
+        self
+    }
+
+    fn map_values(self, f: impl Fn(Self) -> Self) -> Self {
+        match self {
+            Object(map) => Object(
+                map.into_iter()
+                    .map(|(key, value)| (key, f(value)))
+                    .collect(),
+            ),
+            _ => Null,
+        }
+    }
+
+    fn map_keys(self, f: impl Fn(&str) -> String) -> Self {
+        map_keys(self, f).map_or(Null, Object)
+    }
+
     fn flatten(self) -> Self {
         match self {
             Array(vec) => {
@@ -184,7 +1052,6 @@ impl JMESPath for Value {
     }
 
     fn flatten_project(self, projection: impl Fn(Self) -> Self) -> Self {
-        println!("self = {:?}", self);
         match self {
             // This is the LHS
             Array(current) => {
@@ -201,29 +1068,638 @@ impl JMESPath for Value {
                 }
                 // The result list is now the new current result.
                 // Once the flattening operation has been performed, subsequent operations are projected onto the flattened list with the same semantics as a wildcard expression. Thus the difference between [*] and [] is that [] will first flatten sublists in the current result.
-                println!("results = {:?}", results);
                 Array(results).list_project(projection)
             }
             _ => Null,
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json::json;
-
-    fn flatmap() -> Value {
-        json!({"a": "foo", "b": "bar", "c": "baz"})
-    }
-    fn nested_map() -> Value {
-        json!({"a": {"b": {"c": {"d": "value"}}}})
+    fn flatten_n(mut self, depth: usize) -> Self {
+        if !self.is_array() {
+            return Null;
+        }
+        for _ in 0..depth {
+            match &self {
+                Array(vec) if vec.iter().any(Value::is_array) => self.flatten_in_place(),
+                _ => break, // Nothing left to flatten
+            }
+        }
+        self
     }
 
-    #[test]
-    fn identifier() {
-        assert_eq!(flatmap().identify("a"), json!("foo"));
+    fn flatten_in_place(&mut self) {
+        let vec = match self {
+            Array(vec) => vec,
+            other => {
+                *other = Null;
+                return;
+            }
+        };
+        // Once empty inner arrays are gone, every element contributes at least one,
+        // so filling from the back never overwrites an element we haven't read yet.
+        vec.retain(|value| !matches!(value, Array(inner) if inner.is_empty()));
+        let len = vec.len();
+        let flattened_len = vec
+            .iter()
+            .map(|value| match value {
+                Array(inner) => inner.len(),
+                _ => 1,
+            })
+            .sum();
+        vec.resize(flattened_len, Null);
+        let mut write = flattened_len;
+        for read in (0..len).rev() {
+            match mem::take(&mut vec[read]) {
+                Array(inner) => {
+                    for value in inner.into_iter().rev() {
+                        write -= 1;
+                        vec[write] = value;
+                    }
+                }
+                value => {
+                    write -= 1;
+                    vec[write] = value;
+                }
+            }
+        }
+    }
+
+    fn leaves(self) -> Self {
+        let mut leaves = Vec::new();
+        // Explicit stack rather than recursion, so deep documents are fine
+        let mut stack = vec![self];
+        while let Some(value) = stack.pop() {
+            match value {
+                // Push in reverse so that we pop in document order
+                Array(vec) => stack.extend(vec.into_iter().rev()),
+                Object(map) => stack.extend(map.into_iter().map(|(_key, value)| value).rev()),
+                leaf => leaves.push(leaf),
+            }
+        }
+        Array(leaves)
+    }
+
+    fn paths(&self) -> impl Iterator<Item = Vec<PathSegment>> + '_ {
+        // Each node, how it was reached, and how long the path to its parent is
+        let mut stack = vec![(self, None, 0)];
+        let mut path = Vec::new();
+        std::iter::from_fn(move || {
+            while let Some((value, segment, depth)) = stack.pop() {
+                path.truncate(depth);
+                path.extend(segment);
+                let depth = path.len();
+                match value {
+                    Array(vec) => stack.extend(vec.iter().enumerate().rev().map(|(i, value)| {
+                        let index =
+                            isize::try_from(i).expect("Vecs are never longer than isize::MAX");
+                        (value, Some(PathSegment::Index(index)), depth)
+                    })),
+                    Object(map) => {
+                        stack.extend(map.iter().rev().map(|(key, value)| {
+                            (value, Some(PathSegment::Key(key.clone())), depth)
+                        }))
+                    }
+                    _ => return Some(path.clone()),
+                }
+            }
+            None
+        })
+    }
+
+    fn truncate_strings(mut self, max_chars: usize, ellipsis: bool) -> Self {
+        let mut stack = vec![&mut self];
+        while let Some(value) = stack.pop() {
+            match value {
+                Array(vec) => stack.extend(vec.iter_mut()),
+                Object(map) => stack.extend(map.values_mut()),
+                Value::String(s) if s.chars().nth(max_chars).is_some() => {
+                    let keep = match ellipsis {
+                        true => max_chars.saturating_sub(1),
+                        false => max_chars,
+                    };
+                    let (end, _) = s.char_indices().nth(keep).expect("Longer than max_chars");
+                    s.truncate(end);
+                    if ellipsis && max_chars != 0 {
+                        s.push('…')
+                    }
+                }
+                _ => (),
+            }
+        }
+        self
+    }
+
+    fn normalize_numbers(mut self) -> Self {
+        let mut stack = vec![&mut self];
+        while let Some(value) = stack.pop() {
+            match value {
+                Array(vec) => stack.extend(vec.iter_mut()),
+                Object(map) => stack.extend(map.values_mut()),
+                Value::Number(n) => {
+                    let Some(f) = n.as_f64().filter(|_| n.is_f64()) else {
+                        continue;
+                    };
+                    // `MAX as f64` rounds up to a power of two, which is out of range
+                    if f.fract() != 0.0 {
+                        continue;
+                    } else if (i64::MIN as f64..i64::MAX as f64).contains(&f) {
+                        *n = (f as i64).into()
+                    } else if (0.0..u64::MAX as f64).contains(&f) {
+                        *n = (f as u64).into()
+                    }
+                }
+                _ => (),
+            }
+        }
+        self
+    }
+
+    fn redact(mut self, keys: &[&str], replacement: Value) -> Self {
+        let keys = keys
+            .iter()
+            .map(|key| key.to_lowercase())
+            .collect::<Vec<_>>();
+        let mut stack = vec![&mut self];
+        while let Some(value) = stack.pop() {
+            match value {
+                Array(vec) => stack.extend(vec.iter_mut()),
+                Object(map) => {
+                    for (key, value) in map.iter_mut() {
+                        match keys.contains(&key.to_lowercase()) {
+                            true => *value = replacement.clone(),
+                            false => stack.push(value),
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        self
+    }
+
+    fn sort(self) -> Self {
+        self.try_sort().unwrap_or(Null)
+    }
+
+    fn try_sort(self) -> Result<Self, SortError> {
+        let mut vec = match self {
+            Array(vec) => vec,
+            other => return Err(SortError::NotArray(type_name(&other))),
+        };
+        check_sortable(&vec)?;
+        vec.sort_by(compare_sortable);
+        Ok(Array(vec))
+    }
+
+    fn sort_by(self, key: impl Fn(&Self) -> Self) -> Self {
+        sort_by_key(self, key, false)
+    }
+
+    fn sort_by_desc(self, key: impl Fn(&Self) -> Self) -> Self {
+        sort_by_key(self, key, true)
+    }
+
+    fn between(self, lo: &Self, hi: &Self) -> Self {
+        match (self, lo, hi) {
+            (Array(vec), Value::Number(lo), Value::Number(hi)) => Array(
+                vec.into_iter()
+                    .filter(|x| match x {
+                        Value::Number(x) => {
+                            compare_numbers(lo, x).is_le() && compare_numbers(x, hi).is_le()
+                        }
+                        _ => false,
+                    })
+                    .collect(),
+            ),
+            _ => Null,
+        }
+    }
+
+    fn slice_values(self, slice: impl Into<JMESSlice>) -> Self {
+        match self {
+            Object(map) => Array(map.into_iter().map(|(_key, value)| value).collect()).slice(slice),
+            _ => Null,
+        }
+    }
+
+    fn project_typed<T: DeserializeOwned>(
+        self,
+        projection: impl Fn(Self) -> Self,
+    ) -> Result<Vec<T>, serde_json::Error> {
+        use serde::de::{Error as _, Unexpected};
+        let vec = match self {
+            Array(vec) => vec,
+            other => {
+                return Err(serde_json::Error::invalid_type(
+                    Unexpected::Other(type_name(&other)),
+                    &"an array",
+                ))
+            }
+        };
+        let mut typed = Vec::new();
+        for (index, value) in vec.into_iter().enumerate() {
+            match projection(value) {
+                Null => {}
+                value => typed.push(serde_json::from_value(value).map_err(|e| {
+                    serde_json::Error::custom(format_args!("element {index}: {e}"))
+                })?),
+            }
+        }
+        Ok(typed)
+    }
+
+    fn multiselect_hash(self, entries: &[HashEntry<Self>]) -> Self {
+        multiselect_hash(self, entries).map_or(Null, Object)
+    }
+
+    fn group_by(self, key: impl Fn(&Self) -> Self) -> Self {
+        group_by(self, key).map_or(Null, Object)
+    }
+
+    fn windows(self, size: usize) -> Self {
+        match self {
+            Array(vec) if size != 0 => Array(
+                vec.windows(size)
+                    .map(|window| Array(window.to_vec()))
+                    .collect(),
+            ),
+            _ => Null,
+        }
+    }
+
+    fn sample(self, n: usize) -> Self {
+        let Array(vec) = self else {
+            return Null;
+        };
+        let mut sample = Vec::new();
+        let mut seen = HashSet::new();
+        for value in vec {
+            if sample.len() == n {
+                break;
+            }
+            if seen.insert(canonical_string(&value)) {
+                sample.push(value)
+            }
+        }
+        Array(sample)
+    }
+
+    fn rotate(self, by: isize) -> Self {
+        match self {
+            Array(mut vec) if !vec.is_empty() => {
+                let len =
+                    isize::try_from(vec.len()).expect("Vecs are never longer than isize::MAX");
+                // Always in 0..len, so a left rotation even if `by` is negative
+                vec.rotate_left(by.rem_euclid(len) as usize);
+                Array(vec)
+            }
+            array @ Array(_) => array,
+            _ => Null,
+        }
+    }
+
+    fn any(self, predicate: impl Fn(&Self) -> bool) -> Self {
+        match self {
+            Array(vec) => Value::Bool(vec.iter().any(predicate)),
+            _ => Null,
+        }
+    }
+
+    fn all(self, predicate: impl Fn(&Self) -> bool) -> Self {
+        match self {
+            Array(vec) => Value::Bool(vec.iter().all(predicate)),
+            _ => Null,
+        }
+    }
+
+    fn find_key_by_value(self, predicate: impl Fn(&Self) -> bool) -> Self {
+        match self {
+            Object(map) => map
+                .into_iter()
+                .find(|(_, value)| predicate(value))
+                .map_or(Null, |(key, _)| Value::String(key)),
+            _ => Null,
+        }
+    }
+
+    fn pluck(self, key: impl AsRef<str>) -> Self {
+        let key = key.as_ref();
+        self.list_project(|v| lenient_identify(v, key))
+    }
+
+    fn to_records(self, columns: &[&str]) -> Self {
+        match self {
+            Array(vec) => Array(
+                vec.into_iter()
+                    .map(|element| {
+                        // Columns may repeat, so clone rather than move
+                        Array(
+                            columns
+                                .iter()
+                                .map(|column| element.get(*column).cloned().unwrap_or(Null))
+                                .collect(),
+                        )
+                    })
+                    .collect(),
+            ),
+            _ => Null,
+        }
+    }
+
+    fn rename_keys(self, mapping: &HashMap<String, String>) -> Self {
+        match self {
+            Object(map) => Object(
+                map.into_iter()
+                    .map(|(key, value)| match mapping.get(&key) {
+                        Some(renamed) => (renamed.clone(), value),
+                        None => (key, value),
+                    })
+                    .collect(),
+            ),
+            _ => Null,
+        }
+    }
+
+    fn pick(self, keys: &[&str]) -> Self {
+        match self {
+            Object(map) => Object(
+                map.into_iter()
+                    .filter(|(key, _value)| keys.contains(&key.as_str()))
+                    .collect(),
+            ),
+            _ => Null,
+        }
+    }
+
+    fn omit(self, keys: &[&str]) -> Self {
+        match self {
+            Object(map) => Object(
+                map.into_iter()
+                    .filter(|(key, _value)| !keys.contains(&key.as_str()))
+                    .collect(),
+            ),
+            _ => Null,
+        }
+    }
+
+    fn as_sparse_array(self, max_len: usize) -> Self {
+        let Object(map) = self else {
+            return Null;
+        };
+        let mut entries = Vec::with_capacity(map.len());
+        for (key, value) in map {
+            match key.parse::<usize>() {
+                Ok(index) if key == index.to_string() => entries.push((index, value)),
+                _ => return Null,
+            }
+        }
+        let len = entries
+            .iter()
+            .try_fold(0, |len, (index, _)| Some(index.checked_add(1)?.max(len)));
+        let Some(len) = len.filter(|len| *len <= max_len) else {
+            return Null;
+        };
+        let mut vec = vec![Null; len];
+        for (index, value) in entries {
+            vec[index] = value
+        }
+        Array(vec)
+    }
+
+    fn into_array(self) -> Option<Vec<Self>> {
+        match self {
+            Array(vec) => Some(vec),
+            _ => None,
+        }
+    }
+
+    fn into_object(self) -> Option<Map<String, Value>> {
+        match self {
+            Object(map) => Some(map),
+            _ => None,
+        }
+    }
+}
+
+/// Build an object from `pairs`, where later pairs override earlier ones.
+pub fn object(pairs: impl IntoIterator<Item = (impl Into<String>, Value)>) -> Value {
+    Object(
+        pairs
+            .into_iter()
+            .map(|(key, value)| (key.into(), value))
+            .collect(),
+    )
+}
+
+/// The numbers from `start` up to but excluding `end`, in steps of `step`, which may be negative.
+/// A `step` of zero is `null`.
+pub fn range(start: i64, end: i64, step: i64) -> Value {
+    if step == 0 {
+        return Null;
+    }
+    let in_range = |n: &i64| match step > 0 {
+        true => *n < end,
+        false => *n > end,
+    };
+    // Stops rather than overflowing
+    Array(
+        std::iter::successors(Some(start), |n| n.checked_add(step))
+            .take_while(in_range)
+            .map(Value::from)
+            .collect(),
+    )
+}
+
+/// Nest `flat` into arrays of the lengths in `shape`, outermost first, like NumPy's `reshape`,
+/// so `[1, 2, 3, 4]` with `[2, 2]` is `[[1, 2], [3, 4]]`.
+/// `null` unless `flat` has exactly as many elements as `shape` needs.
+/// An empty `shape` needs one, which is returned as-is.
+///
+/// The outer dimensions may not multiply to more than that either, or one if `flat` is empty,
+/// so a zero dimension can't ask for a huge number of empty arrays, like `[usize::MAX, 0]` would.
+pub fn reshape(flat: Vec<Value>, shape: &[usize]) -> Value {
+    let len = |dims: &[usize]| {
+        dims.iter()
+            .try_fold(1_usize, |len, dim| len.checked_mul(*dim))
+    };
+    let max = flat.len().max(1);
+    if len(shape) != Some(flat.len())
+        || (1..shape.len()).any(|i| len(&shape[..i]).is_none_or(|outer| outer > max))
+    {
+        return Null;
+    }
+    // Group the innermost dimension first
+    let mut level = flat;
+    for (i, dim) in shape.iter().enumerate().rev() {
+        let Some(outer) = len(&shape[..i]) else {
+            return Null;
+        };
+        let mut elements = level.into_iter();
+        level = (0..outer)
+            .map(|_| Array(elements.by_ref().take(*dim).collect()))
+            .collect();
+    }
+    level
+        .pop()
+        .expect("The outermost dimension is a single array")
+}
+
+/// Roughly how many bytes `serde_json::to_string(value)` would be, without serializing it.
+///
+/// Exact unless strings need escaping, which makes this an underestimate.
+pub fn estimated_json_len(value: &Value) -> usize {
+    let mut len = 0;
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        len += match value {
+            Null => 4,
+            Value::Bool(true) => 4,
+            Value::Bool(false) => 5,
+            Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+                (Some(u), _) => u.checked_ilog10().unwrap_or(0) as usize + 1,
+                (_, Some(i)) => i.unsigned_abs().checked_ilog10().unwrap_or(0) as usize + 2,
+                // Floats are too fiddly to count digits of
+                _ => number.to_string().len(),
+            },
+            Value::String(s) => s.len() + 2,
+            Array(vec) => {
+                stack.extend(vec);
+                // Brackets and commas
+                2 + vec.len().saturating_sub(1)
+            }
+            Object(map) => {
+                stack.extend(map.values());
+                // Braces, commas, quoted keys and colons
+                2 + map.len().saturating_sub(1) + map.keys().map(|key| key.len() + 3).sum::<usize>()
+            }
+        }
+    }
+    len
+}
+
+/// A deterministic serialization of `value`, with object keys sorted, for use as a map key.
+///
+/// We can't rely on `serde_json`'s own ordering, because any crate in the build may enable its `preserve_order` feature.
+/// Numbers are written by value, like [`semantically_eq`] compares them, so `1` and `1.0` have the same key.
+///
+/// This is how [`JMESPath::sample`] tells elements apart.
+pub fn canonical_string(value: &Value) -> String {
+    fn write(value: &Value, out: &mut String) {
+        match value {
+            Array(vec) => {
+                out.push('[');
+                for (i, value) in vec.iter().enumerate() {
+                    if i != 0 {
+                        out.push(',');
+                    }
+                    write(value, out);
+                }
+                out.push(']');
+            }
+            Object(map) => {
+                let mut entries = map.iter().collect::<Vec<_>>();
+                entries.sort_by_key(|(key, _value)| *key);
+                out.push('{');
+                for (i, (key, value)) in entries.into_iter().enumerate() {
+                    if i != 0 {
+                        out.push(',');
+                    }
+                    out.push_str(&serde_json::to_string(key).expect("Strings always serialize"));
+                    out.push(':');
+                    write(value, out);
+                }
+                out.push('}');
+            }
+            Value::Number(n) => {
+                let integer = n
+                    .as_i64()
+                    .map(i128::from)
+                    .or_else(|| n.as_u64().map(i128::from));
+                let float = n.as_f64().expect("JSON numbers are finite");
+                match integer {
+                    Some(integer) => out.push_str(&integer.to_string()),
+                    // Whole floats are written as integers, which also turns `-0` into `0`
+                    None if float.fract() == 0.0 && float.abs() < 2_f64.powi(64) => {
+                        out.push_str(&(float as i128).to_string())
+                    }
+                    None => out.push_str(&n.to_string()),
+                }
+            }
+            scalar => out.push_str(&scalar.to_string()),
+        }
+    }
+    let mut out = String::new();
+    write(value, &mut out);
+    out
+}
+
+/// Every leaf which differs between `before` and `after`, as `(path, old, new)`.
+///
+/// Paths are JMESPath expressions, like `a.b[0]`, or `@` if the documents differ at the root.
+/// A key or element which was added or removed is reported whole, with `null` on the side where it is missing,
+/// even if its value is `null`.
+pub fn diff(before: &Value, after: &Value) -> Vec<(String, Value, Value)> {
+    fn walk(
+        path: &mut Vec<PathSegment>,
+        before: &Value,
+        after: &Value,
+        out: &mut Vec<(String, Value, Value)>,
+    ) {
+        match (before, after) {
+            (Object(before), Object(after)) => {
+                for (key, old) in before {
+                    path.push(PathSegment::Key(key.clone()));
+                    match after.get(key) {
+                        Some(new) => walk(path, old, new, out),
+                        None => out.push((render_path(path), old.clone(), Null)),
+                    }
+                    path.pop();
+                }
+                for (key, new) in after.iter().filter(|(key, _)| !before.contains_key(*key)) {
+                    path.push(PathSegment::Key(key.clone()));
+                    out.push((render_path(path), Null, new.clone()));
+                    path.pop();
+                }
+            }
+            (Array(before), Array(after)) => {
+                for i in 0..before.len().max(after.len()) {
+                    let index = isize::try_from(i).expect("Vecs are never longer than isize::MAX");
+                    path.push(PathSegment::Index(index));
+                    match (before.get(i), after.get(i)) {
+                        (Some(old), Some(new)) => walk(path, old, new, out),
+                        (Some(old), None) => out.push((render_path(path), old.clone(), Null)),
+                        (None, Some(new)) => out.push((render_path(path), Null, new.clone())),
+                        (None, None) => unreachable!("Within the longer array"),
+                    }
+                    path.pop();
+                }
+            }
+            (before, after) if !semantically_eq(before, after) => {
+                out.push((render_path(path), before.clone(), after.clone()))
+            }
+            _ => (),
+        }
+    }
+    let mut out = Vec::new();
+    walk(&mut Vec::new(), before, after, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn flatmap() -> Value {
+        json!({"a": "foo", "b": "bar", "c": "baz"})
+    }
+    fn nested_map() -> Value {
+        json!({"a": {"b": {"c": {"d": "value"}}}})
+    }
+
+    #[test]
+    fn identifier() {
+        assert_eq!(flatmap().identify("a"), json!("foo"));
         assert_eq!(flatmap().identify("d"), json!(null));
         assert_eq!(
             nested_map()
@@ -234,6 +1710,71 @@ mod tests {
             json!("value")
         )
     }
+    #[test]
+    #[cfg_attr(
+        feature = "strict-debug",
+        should_panic(expected = "Expected an array to index [0], but found object")
+    )]
+    fn index_wrong_type() {
+        assert_eq!(flatmap().index(0), Null);
+    }
+
+    #[test]
+    #[cfg_attr(
+        feature = "strict-debug",
+        should_panic(expected = r#"Expected an object to identify "a", but found array"#)
+    )]
+    fn identify_wrong_type() {
+        assert_eq!(json!([]).identify("a"), Null);
+    }
+
+    #[test]
+    fn strict_debug_leaves_search_lenient() {
+        assert_eq!(crate::search("a[0]", flatmap()), Ok(Null));
+        assert_eq!(crate::search("[0].a", flatmap()), Ok(Null));
+    }
+
+    #[test]
+    fn identify_or() {
+        assert_eq!(json!({}).identify_or("x", json!(0)), json!(0));
+        assert_eq!(json!({"x": null}).identify_or("x", json!(0)), json!(0));
+        assert_eq!(json!({"x": false}).identify_or("x", json!(0)), json!(false));
+        assert_eq!(json!({"x": ""}).identify_or("x", json!(0)), json!(""));
+        assert_eq!(flatmap().identify_or("a", json!(0)), json!("foo"));
+        assert_eq!(json!([]).identify_or("x", json!(0)), json!(0));
+    }
+
+    #[test]
+    fn pointer() {
+        assert_eq!(complex().pointer("/a/b/c/0/d/1"), json!([1, 2]));
+        assert_eq!(complex().pointer("/a/b/c/0/d/1/0"), json!(1));
+        assert_eq!(complex().pointer(""), complex());
+        assert_eq!(complex().pointer("/a/x"), Null);
+        assert_eq!(complex().pointer("/a/b/c/2"), Null);
+        assert_eq!(complex().pointer("/a/b/c/-1"), Null);
+        assert_eq!(complex().pointer("/a/b/c/01"), Null);
+        assert_eq!(complex().pointer("a"), Null);
+        let escaped = json!({"a/b": {"c~d": {"": 1}}, "~1": 2});
+        assert_eq!(escaped.clone().pointer("/a~1b/c~0d/"), json!(1));
+        assert_eq!(escaped.pointer("/~01"), json!(2));
+    }
+
+    #[test]
+    fn identify_prefix() {
+        let headers = json!({"header_1": "a", "header_2": "b", "other": "c"});
+        assert_eq!(
+            headers.clone().identify_prefix("header_"),
+            json!(["a", "b"])
+        );
+        assert_eq!(headers.clone().identify_prefix("oth"), json!(["c"]));
+        assert_eq!(headers.clone().identify_prefix("x"), json!([]));
+        assert_eq!(
+            headers.identify_prefix("").as_array().map(Vec::len),
+            Some(3)
+        );
+        assert_eq!(array().identify_prefix("a"), Null);
+    }
+
     fn array() -> Value {
         json!(["a", "b", "c", "d", "e", "f"])
     }
@@ -246,6 +1787,280 @@ mod tests {
         assert_eq!(array().index(-10), json!(null));
     }
 
+    #[test]
+    fn index_negative_boundaries() {
+        // `array()` has six elements, so `-6` is the first, and `-7` is just out of bounds
+        assert_eq!(array().index(-6), json!("a"));
+        assert_eq!(array().index(-7), Null);
+        assert_eq!(array().index(5), json!("f"));
+        assert_eq!(array().index(6), Null);
+        // Everything else which indexes agrees
+        assert_eq!(crate::search("[-6]", array()), Ok(json!("a")));
+        assert_eq!(crate::search("[-7]", array()), Ok(Null));
+        assert_eq!(array().get_path(&[PathSegment::Index(-6)]), Ok(json!("a")));
+        assert_eq!(
+            array().get_path(&[PathSegment::Index(-7)]),
+            Err(PathError::OutOfBounds {
+                path: "@".into(),
+                index: -7
+            })
+        );
+        assert_eq!(array().index_with(-7, OobPolicy::Clamp), json!("a"));
+        assert_eq!(array().index_with(-7, OobPolicy::Wrap), json!("f"));
+        // Slices clamp instead, so starting further back still starts at the first
+        assert_eq!(array().slice(-6..), array());
+        assert_eq!(array().slice(-7..), array());
+        assert_eq!(array().slice(-6..-5), json!(["a"]));
+        assert_eq!(array().slice(..-6), json!([]));
+    }
+
+    #[test]
+    fn index_extremes() {
+        assert_eq!(array().index(isize::MIN), json!(null));
+        assert_eq!(array().index(isize::MAX), json!(null));
+        assert_eq!(json!([]).index(isize::MIN), json!(null));
+        assert_eq!(array().index_with(isize::MIN, OobPolicy::Null), json!(null));
+        assert_eq!(array().index_with(isize::MAX, OobPolicy::Null), json!(null));
+        assert_eq!(array().index_with(isize::MIN, OobPolicy::Clamp), json!("a"));
+        assert_eq!(array().index_with(isize::MAX, OobPolicy::Clamp), json!("f"));
+        // `isize::MIN.rem_euclid(6)` is 4, and `isize::MAX.rem_euclid(6)` is 1
+        assert_eq!(array().index_with(isize::MIN, OobPolicy::Wrap), json!("e"));
+        assert_eq!(array().index_with(isize::MAX, OobPolicy::Wrap), json!("b"));
+    }
+
+    #[test]
+    fn index_with() {
+        assert_eq!(array().index_with(10, OobPolicy::Null), json!(null));
+        assert_eq!(array().index_with(-10, OobPolicy::Null), json!(null));
+        assert_eq!(array().index_with(10, OobPolicy::Clamp), json!("f"));
+        assert_eq!(array().index_with(-10, OobPolicy::Clamp), json!("a"));
+        assert_eq!(array().index_with(10, OobPolicy::Wrap), json!("e"));
+        assert_eq!(array().index_with(-10, OobPolicy::Wrap), json!("c"));
+        assert_eq!(array().index_with(1, OobPolicy::Wrap), json!("b"));
+        assert_eq!(json!([]).index_with(0, OobPolicy::Clamp), json!(null));
+    }
+
+    #[test]
+    fn windows() {
+        assert_eq!(
+            json!([1, 2, 3, 4]).windows(2),
+            json!([[1, 2], [2, 3], [3, 4]])
+        );
+        assert_eq!(json!([1, 2]).windows(3), json!([]));
+        assert_eq!(json!([1, 2]).windows(0), json!(null));
+        assert_eq!(flatmap().windows(1), json!(null));
+    }
+
+    #[test]
+    fn sample() {
+        assert_eq!(json!([1, 1, 2, 2, 3]).sample(2), json!([1, 2]));
+        assert_eq!(json!([1, 1, 2, 2, 3]).sample(10), json!([1, 2, 3]));
+        assert_eq!(
+            json!([{"a": 1}, {"a": 1.0}, "1", [1], 1]).sample(4),
+            json!([{"a": 1}, "1", [1], 1])
+        );
+        assert_eq!(json!([1, 2]).sample(0), json!([]));
+        assert_eq!(flatmap().sample(1), Null);
+    }
+
+    #[test]
+    fn rotate() {
+        assert_eq!(json!([1, 2, 3, 4]).rotate(1), json!([2, 3, 4, 1]));
+        assert_eq!(json!([1, 2, 3, 4]).rotate(-1), json!([4, 1, 2, 3]));
+        assert_eq!(json!([1, 2, 3, 4]).rotate(-6), json!([3, 4, 1, 2]));
+        assert_eq!(json!([1, 2, 3, 4]).rotate(4), json!([1, 2, 3, 4]));
+        assert_eq!(json!([1]).rotate(isize::MIN), json!([1]));
+        assert_eq!(json!([]).rotate(3), json!([]));
+        assert_eq!(flatmap().rotate(1), Null);
+    }
+
+    #[test]
+    fn as_sparse_array() {
+        const MAX: usize = 1 << 20;
+        assert_eq!(
+            json!({"0": "a", "2": "c"}).as_sparse_array(MAX),
+            json!(["a", null, "c"])
+        );
+        // Not sorted lexically
+        assert_eq!(
+            json!({"10": 10, "9": 9}).as_sparse_array(MAX).index(-2),
+            json!(9)
+        );
+        assert_eq!(json!({}).as_sparse_array(MAX), json!([]));
+        assert_eq!(json!({"0": "a", "b": "b"}).as_sparse_array(MAX), Null);
+        assert_eq!(json!({"01": "a"}).as_sparse_array(MAX), Null);
+        assert_eq!(json!({"-1": "a"}).as_sparse_array(MAX), Null);
+        // Longer than can be indexed at all, whatever `max_len` is
+        assert_eq!(
+            json!({"18446744073709551615": 1}).as_sparse_array(usize::MAX),
+            Null
+        );
+        assert_eq!(
+            json!({"0": 0, "18446744073709551615": 1}).as_sparse_array(usize::MAX),
+            Null
+        );
+        // Longer than `max_len`
+        assert_eq!(json!({"9": 1}).as_sparse_array(10).index(-1), json!(1));
+        assert_eq!(json!({"10": 1}).as_sparse_array(10), Null);
+        assert_eq!(json!({"99999999999": 1}).as_sparse_array(MAX), Null);
+        assert_eq!(json!({}).as_sparse_array(0), json!([]));
+        assert_eq!(array().as_sparse_array(MAX), Null);
+    }
+
+    #[test]
+    fn rename_keys() {
+        let mapping = HashMap::from([("first".to_string(), "name".to_string())]);
+        assert_eq!(
+            json!({"first": "x", "last": "y"}).rename_keys(&mapping),
+            json!({"name": "x", "last": "y"})
+        );
+        let mapping = HashMap::from([("a".to_string(), "b".to_string())]);
+        assert_eq!(
+            json!({"a": 1, "b": 2}).rename_keys(&mapping),
+            json!({"b": 2})
+        );
+        assert_eq!(array().rename_keys(&mapping), json!(null));
+    }
+
+    #[test]
+    fn project_mask() {
+        assert_eq!(
+            complex().project_mask(&json!({"a": {"b": {"c": {"d": true}}}})),
+            complex()
+        );
+        let data = json!({
+            "name": "widget",
+            "secret": "hunter2",
+            "parts": [{"id": 1, "cost": 5}, {"id": 2}, "loose"],
+            "meta": {"created": "today", "tags": ["a"]}
+        });
+        assert_eq!(
+            data.clone().project_mask(&json!({
+                "name": true,
+                "secret": false,
+                "parts": {"id": true},
+                "meta": {"tags": true},
+                "missing": true
+            })),
+            json!({
+                "name": "widget",
+                "parts": [{"id": 1}, {"id": 2}, null],
+                "meta": {"tags": ["a"]}
+            })
+        );
+        assert_eq!(
+            data.clone().project_mask(&json!({"name": {"first": true}})),
+            json!({"name": null})
+        );
+        assert_eq!(data.clone().project_mask(&json!(true)), data);
+        assert_eq!(data.project_mask(&json!({})), json!({}));
+        assert_eq!(json!(1).project_mask(&json!(false)), Null);
+    }
+
+    #[test]
+    fn with_defaults() {
+        let defaults = json!({"timeout": 30, "nested": {"a": 1}});
+        assert_eq!(
+            json!({"nested": {"b": 2}}).with_defaults(&defaults),
+            json!({"timeout": 30, "nested": {"a": 1, "b": 2}})
+        );
+        assert_eq!(
+            json!({"timeout": null, "nested": 5}).with_defaults(&defaults),
+            json!({"timeout": null, "nested": 5})
+        );
+        assert_eq!(array().with_defaults(&defaults), array());
+    }
+
+    #[test]
+    fn map_values() {
+        let double = |value: Value| match value.as_i64() {
+            Some(i) => json!(i * 2),
+            None => value,
+        };
+        assert_eq!(
+            json!({"a": 1, "b": "x", "c": 3}).map_values(double),
+            json!({"a": 2, "b": "x", "c": 6})
+        );
+        assert_eq!(array().map_values(double), Null);
+    }
+
+    #[test]
+    fn group_by() {
+        let state = |item: &Value| item["state"].clone();
+        assert_eq!(
+            json!([{"state": "WA", "n": 1}, {"state": "NY", "n": 2}, {"state": "WA", "n": 3}])
+                .group_by(state),
+            json!({
+                "WA": [{"state": "WA", "n": 1}, {"state": "WA", "n": 3}],
+                "NY": [{"state": "NY", "n": 2}]
+            })
+        );
+        // The last person has no `last`
+        assert_eq!(
+            list_project_example()
+                .identify("people")
+                .group_by(|person| person["last"].clone()),
+            Null
+        );
+        assert_eq!(json!([]).group_by(state), json!({}));
+        assert_eq!(flatmap().group_by(state), Null);
+    }
+
+    #[test]
+    fn map_keys() {
+        assert_eq!(
+            flatmap().map_keys(str::to_uppercase),
+            json!({"A": "foo", "B": "bar", "C": "baz"})
+        );
+        assert_eq!(
+            json!({"a": 1, "b": 2}).map_keys(|_| "key".into()),
+            json!({"key": 2})
+        );
+        assert_eq!(array().map_keys(str::to_uppercase), Null);
+    }
+
+    #[test]
+    fn pick_omit() {
+        assert_eq!(
+            flatmap().pick(&["a", "b", "missing"]),
+            json!({"a": "foo", "b": "bar"})
+        );
+        assert_eq!(flatmap().omit(&["a", "b", "missing"]), json!({"c": "baz"}));
+        assert_eq!(array().pick(&["a"]), json!(null));
+        assert_eq!(array().omit(&["a"]), json!(null));
+    }
+
+    #[test]
+    fn normalize_index() {
+        use super::normalize_index;
+        assert_eq!(normalize_index(0, 3), Some(0));
+        assert_eq!(normalize_index(2, 3), Some(2));
+        assert_eq!(normalize_index(3, 3), None);
+        assert_eq!(normalize_index(-1, 3), Some(2));
+        assert_eq!(normalize_index(-3, 3), Some(0));
+        assert_eq!(normalize_index(-4, 3), None);
+        assert_eq!(normalize_index(0, 0), None);
+        assert_eq!(normalize_index(-1, 0), None);
+        assert_eq!(normalize_index(isize::MIN, 3), None);
+        assert_eq!(normalize_index(isize::MAX, 3), None);
+        assert_eq!(
+            normalize_index(isize::MIN, usize::MAX),
+            Some(usize::MAX / 2)
+        );
+    }
+
+    #[test]
+    fn into_collections() {
+        assert_eq!(json!([1, 2]).into_array(), Some(vec![json!(1), json!(2)]));
+        assert_eq!(flatmap().into_array(), None);
+        assert_eq!(
+            json!({"a": 1}).into_object(),
+            Some(Map::from_iter([("a".into(), json!(1))]))
+        );
+        assert_eq!(array().into_object(), None);
+        assert_eq!(json!(null).into_object(), None);
+    }
+
     fn complex() -> Value {
         json!({"a": {
           "b": {
@@ -294,6 +2109,93 @@ mod tests {
         assert_eq!(res, Err(ParseJMESSliceError::StepNotAllowedToBeZero));
     }
 
+    #[test]
+    fn parse_jmes_slice_optional_parts() {
+        let parse = |s: &str| s.parse::<JMESSlice>();
+        assert_eq!(parse(":"), Ok(JMESSlice::default()));
+        assert_eq!(parse("::"), Ok(JMESSlice::default()));
+        assert_eq!(parse("1:"), Ok((1..).into()));
+        assert_eq!(parse("1::"), Ok((1..).into()));
+        assert_eq!(parse(":2:"), Ok((..2).into()));
+        assert_eq!(parse(""), Err(ParseJMESSliceError::InvalidFormat));
+        assert_eq!(parse("1"), Err(ParseJMESSliceError::InvalidFormat));
+        assert_eq!(parse(":::"), Err(ParseJMESSliceError::InvalidFormat));
+    }
+
+    #[test]
+    fn parse_jmes_slice_overflow() {
+        let huge = "9".repeat(40);
+        for s in [format!(":{}", huge), format!("::-{}", huge)] {
+            assert_eq!(
+                s.parse::<JMESSlice>(),
+                Err(ParseJMESSliceError::InvalidFormat)
+            );
+        }
+        assert_eq!(
+            "99999999999999999999:0".parse::<JMESSlice>(),
+            Err(ParseJMESSliceError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn display_jmes_slice() {
+        for s in [":", "1:", ":-2", "::-1", "1:2:3"] {
+            assert_eq!(s.parse::<JMESSlice>().unwrap().to_string(), s);
+        }
+        assert_eq!(JMESSlice::default().to_string(), ":");
+    }
+
+    #[test]
+    fn jmes_slice_ord() {
+        let mut slices = ["1:", "::-1", ":2", "-1:", ":", "::2", "1:2"]
+            .map(|s| s.parse::<JMESSlice>().unwrap())
+            .to_vec();
+        slices.sort();
+        assert_eq!(
+            slices.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [":", "::-1", "::2", ":2", "-1:", "1:", "1:2"]
+        );
+    }
+
+    #[test]
+    fn jmes_slice_from_usize_range() {
+        assert_eq!(JMESSlice::from(0usize..4), JMESSlice::from(0isize..4));
+        assert_eq!(JMESSlice::from(2usize..), JMESSlice::from(2isize..));
+        assert_eq!(JMESSlice::from(..3usize), JMESSlice::from(..3isize));
+        assert_eq!(JMESSlice::from(..usize::MAX), JMESSlice::from(..isize::MAX));
+        let len = slice_example().as_array().unwrap().len();
+        assert_eq!(slice_example().slice(1..len), json!([1, 2, 3]));
+    }
+
+    #[test]
+    fn jmes_slice_from_value() {
+        assert_eq!(
+            JMESSlice::try_from(&json!({"start": 1, "end": 3})),
+            Ok((1..3).into())
+        );
+        assert_eq!(
+            JMESSlice::try_from(&json!({"step": -1, "end": null})),
+            Ok(JMESSlice::reversed())
+        );
+        assert_eq!(JMESSlice::try_from(&json!({})), Ok(JMESSlice::default()));
+        assert_eq!(
+            JMESSlice::try_from(&json!({"step": 0})),
+            Err(ParseJMESSliceError::StepNotAllowedToBeZero)
+        );
+        assert_eq!(
+            JMESSlice::try_from(&json!({"start": "1"})),
+            Err(ParseJMESSliceError::InvalidFormat)
+        );
+        assert_eq!(
+            JMESSlice::try_from(&json!({"start": 1.5})),
+            Err(ParseJMESSliceError::InvalidFormat)
+        );
+        assert_eq!(
+            JMESSlice::try_from(&json!([1, 3])),
+            Err(ParseJMESSliceError::InvalidFormat)
+        );
+    }
+
     fn slice_example() -> Value {
         json!([0, 1, 2, 3])
     }
@@ -334,6 +2236,73 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn with_step() -> anyhow::Result<()> {
+        let ten = Value::from_iter(0..10);
+        assert_eq!(
+            ten.clone().slice(JMESSlice::from(0..10).with_step(2)?),
+            json!([0, 2, 4, 6, 8])
+        );
+        assert_eq!(
+            JMESSlice::from(..3).with_step(-1)?,
+            ":3:-1".parse::<JMESSlice>()?
+        );
+        assert_eq!(
+            ten.slice(JMESSlice::from(8..).with_step(-3)?),
+            json!([8, 5, 2])
+        );
+        assert_eq!(
+            JMESSlice::from(0..10).with_step(0),
+            Err(ParseJMESSliceError::StepNotAllowedToBeZero)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn apply_slice() {
+        assert_eq!(
+            JMESSlice::reversed().apply_to(array()),
+            json!(["f", "e", "d", "c", "b", "a"])
+        );
+        let slice = JMESSlice::from(..1);
+        assert_eq!(
+            json!([[0, 1], [2, 3]]).list_project(|v| slice.apply_to(v)),
+            json!([[0], [2]])
+        );
+    }
+
+    #[test]
+    fn slicing_negative_step_default_start() {
+        // An absent start with a negative step starts from the back...
+        assert_eq!(
+            slice_example().slice("::-1".parse::<JMESSlice>().unwrap()),
+            json!([3, 2, 1, 0])
+        );
+        // ...but an explicit 0 starts from the front, and immediately stops
+        assert_eq!(
+            slice_example().slice("0::-1".parse::<JMESSlice>().unwrap()),
+            json!([0])
+        );
+        assert_eq!(
+            slice_example().slice(":0:-1".parse::<JMESSlice>().unwrap()),
+            json!([3, 2, 1])
+        );
+        assert_eq!(
+            slice_example().slice("2::-1".parse::<JMESSlice>().unwrap()),
+            json!([2, 1, 0])
+        );
+    }
+
+    #[test]
+    fn slicing_oversized_step() {
+        // The first step always lands on the start, and the next is past the end
+        assert_eq!(array().slice_str_expr("::100"), json!(["a"]));
+        assert_eq!(array().slice_str_expr("::-100"), json!(["f"]));
+        assert_eq!(array().slice_str_expr("1::100"), json!(["b"]));
+        assert_eq!(array().slice_str_expr("-2::-100"), json!(["e"]));
+        assert_eq!(json!([]).slice_str_expr("::100"), json!([]));
+    }
+
     fn list_project_example() -> Value {
         json!({
           "people": [
@@ -347,13 +2316,145 @@ mod tests {
     }
 
     #[test]
-    fn list_projection() {
+    fn list_projection() {
+        assert_eq!(
+            list_project_example()
+                .identify("people")
+                .list_project(|v| v.identify("first")),
+            json!(["James", "Jacob", "Jayden"])
+        );
+    }
+
+    #[test]
+    fn list_projection_indexed() {
+        assert_eq!(
+            array().list_project_indexed(|i, v| json!(format!("{}:{}", i, v.as_str().unwrap()))),
+            json!(["0:a", "1:b", "2:c", "3:d", "4:e", "5:f"])
+        );
+        // Indices are of the original array, even once nulls are dropped
+        assert_eq!(
+            array().list_project_indexed(|i, v| if i % 2 == 0 { Null } else { v }),
+            json!(["b", "d", "f"])
+        );
+        assert_eq!(flatmap().list_project_indexed(|_, v| v), Null);
+    }
+
+    #[test]
+    fn list_project_keep_nulls() {
+        let people = list_project_example().identify("people");
+        assert_eq!(
+            people
+                .clone()
+                .list_project_keep_nulls(|v| v.identify("first")),
+            json!(["James", "Jacob", "Jayden", null])
+        );
+        assert_eq!(
+            people
+                .clone()
+                .list_project_keep_nulls(|v| v.identify("first")),
+            crate::search("map(&first, @)", people).unwrap()
+        );
+        assert_eq!(flatmap().list_project_keep_nulls(|v| v), Null);
+    }
+
+    #[test]
+    fn project_typed() {
+        assert_eq!(
+            list_project_example()
+                .identify("people")
+                .project_typed::<String>(|v| v.identify("first"))
+                .unwrap(),
+            vec!["James", "Jacob", "Jayden"]
+        );
+        let error = json!([{"first": "James"}, {}, {"first": 1}, {"first": true}])
+            .project_typed::<String>(|v| v.identify("first"))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "element 2: invalid type: integer `1`, expected a string"
+        );
+        assert_eq!(
+            flatmap()
+                .project_typed::<String>(|v| v)
+                .unwrap_err()
+                .to_string(),
+            "invalid type: object, expected an array"
+        );
+    }
+
+    #[test]
+    fn any_all() {
+        let first_starts_with_j = |v: &Value| {
+            v.get("first")
+                .and_then(Value::as_str)
+                .is_some_and(|first| first.starts_with('J'))
+        };
+        let people = list_project_example().identify("people");
+        assert_eq!(people.clone().any(first_starts_with_j), json!(true));
+        assert_eq!(people.clone().all(first_starts_with_j), json!(false));
+        assert_eq!(people.slice(..3).all(first_starts_with_j), json!(true));
+        assert_eq!(json!([]).any(first_starts_with_j), json!(false));
+        assert_eq!(json!([]).all(first_starts_with_j), json!(true));
+        assert_eq!(flatmap().any(first_starts_with_j), json!(null));
+    }
+
+    #[test]
+    fn find_key_by_value() {
+        assert_eq!(
+            json!({"a": 1, "b": 2}).find_key_by_value(|v| *v == json!(2)),
+            json!("b")
+        );
+        // The first in map order
+        assert_eq!(
+            flatmap().find_key_by_value(|v| v.as_str().is_some_and(|s| s.starts_with('b'))),
+            json!("b")
+        );
+        assert_eq!(flatmap().find_key_by_value(|v| v.is_null()), Null);
+        assert_eq!(json!([2]).find_key_by_value(|v| *v == json!(2)), Null);
+    }
+
+    #[test]
+    fn pluck() {
+        assert_eq!(
+            list_project_example().identify("people").pluck("first"),
+            json!(["James", "Jacob", "Jayden"])
+        );
+        assert_eq!(list_project_example().pluck("people"), json!(null));
+        // Elements which aren't objects are skipped, even under `strict-debug`
+        assert_eq!(json!([{"a": 1}, 2]).pluck("a"), json!([1]));
+    }
+
+    #[test]
+    fn to_records() {
         assert_eq!(
             list_project_example()
                 .identify("people")
-                .list_project(|v| v.identify("first")),
-            json!(["James", "Jacob", "Jayden"])
+                .to_records(&["first", "last"]),
+            json!([
+                ["James", "d"],
+                ["Jacob", "e"],
+                ["Jayden", "f"],
+                [null, null]
+            ])
+        );
+        assert_eq!(
+            json!([{"a": 1, "b": 2}, 3]).to_records(&["b", "a", "b"]),
+            json!([[2, 1, 2], [null, null, null]])
+        );
+        assert_eq!(json!([{"a": 1}]).to_records(&[]), json!([[]]));
+        assert_eq!(list_project_example().to_records(&["people"]), Null);
+    }
+
+    #[test]
+    fn contains_key() {
+        let people = list_project_example().identify("people");
+        assert_eq!(
+            people.clone().index(-1).contains_key("missing"),
+            json!(true)
         );
+        assert_eq!(people.clone().index(-1).contains_key("first"), json!(false));
+        assert_eq!(json!({"first": null}).contains_key("first"), json!(true));
+        assert_eq!(people.contains_key("first"), json!(null));
     }
 
     #[test]
@@ -366,6 +2467,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn multiselect_hash() {
+        let person = list_project_example().identify("people").index(0);
+        assert_eq!(
+            person.clone().multiselect_hash(&[
+                HashEntry::Spread(&|v| v),
+                HashEntry::Named("last", &|_| json!("overridden")),
+                HashEntry::Named("initial", &|v| v.identify("first").slice(..1)),
+            ]),
+            json!({"first": "James", "last": "overridden", "initial": null})
+        );
+        assert_eq!(
+            person.multiselect_hash(&[
+                HashEntry::Named("first", &|_| json!("overridden")),
+                HashEntry::Spread(&|v| v),
+            ]),
+            json!({"first": "James", "last": "d"})
+        );
+        assert_eq!(
+            json!(null).multiselect_hash(&[HashEntry::Spread(&|v| v)]),
+            json!(null)
+        );
+    }
+
     fn object_projection_example() -> Value {
         json!({
           "ops": {
@@ -386,6 +2511,17 @@ mod tests {
         )
     }
 
+    #[test]
+    fn slice_values() {
+        assert_eq!(
+            object_projection_example()
+                .identify("ops")
+                .slice_values(":2".parse::<JMESSlice>().unwrap()),
+            json!([{"numArgs": 2}, {"numArgs": 3}])
+        );
+        assert_eq!(array().slice_values(..2), json!(null));
+    }
+
     fn flatten_projection_example() -> Value {
         json!({
           "reservations": [
@@ -418,9 +2554,8 @@ mod tests {
         assert_eq!(
             flatten_projection_example()
                 .identify("reservations")
-                .list_project(|v| v
-                    .identify("instances")
-                    .flatten_project(|v| v.identify("state"))), // reservations[*].instances[].state
+                .list_project(|v| v.identify("instances"))
+                .flatten_project(|v| v.identify("state")), // reservations[*].instances[].state
             json!(["running", "stopped", "terminated", "running"]),
         );
     }
@@ -438,11 +2573,88 @@ mod tests {
         assert_eq!(
             nested_list_example()
                 .flatten_project(|v| v)
-                .flatten_project(|v| v), // Why isn't this nested?
+                .flatten_project(|v| v), // Each flattens one more level
             json!([0, 1, 2, 3, 4, 5, 6, 7]),
         )
     }
 
+    #[test]
+    #[allow(deprecated)]
+    fn flatten_in_place() {
+        for value in [
+            nested_list_example(),
+            json!([[], 0, [], [1, [2]], []]),
+            json!([[], []]),
+            json!([]),
+            flatmap(),
+        ] {
+            let mut in_place = value.clone();
+            in_place.flatten_in_place();
+            assert_eq!(in_place, value.flatten());
+        }
+    }
+
+    /// Pins down the spec's flatten operator, `[]`.
+    /// Every flatten should agree, only [`JMESPath::flatten_project`] then drops `null`s like any projection.
+    #[allow(deprecated)]
+    mod flatten_spec {
+        use super::*;
+
+        fn assert_flattens(value: Value, expected: Value) {
+            assert_eq!(value.clone().flatten(), expected, "flatten {}", value);
+            let mut in_place = value.clone();
+            in_place.flatten_in_place();
+            assert_eq!(in_place, expected, "flatten_in_place {}", value);
+            assert_eq!(value.clone().flatten_n(1), expected, "flatten_n {}", value);
+        }
+
+        #[test]
+        fn one_level() {
+            assert_flattens(json!([[1, 2], 3]), json!([1, 2, 3]));
+            assert_flattens(json!([[1], [2, 3]]), json!([1, 2, 3]));
+        }
+
+        #[test]
+        fn empty_sub_arrays_are_elided() {
+            assert_flattens(json!([[], [1]]), json!([1]));
+            assert_flattens(json!([[], 1, []]), json!([1]));
+            assert_flattens(json!([[]]), json!([]));
+        }
+
+        #[test]
+        fn only_one_level() {
+            assert_flattens(json!([[[1]]]), json!([[1]]));
+            assert_flattens(json!([[[]]]), json!([[]]));
+        }
+
+        #[test]
+        fn empty() {
+            assert_flattens(json!([]), json!([]));
+        }
+
+        #[test]
+        fn scalars_pass_through() {
+            assert_flattens(
+                json!([1, "a", {"b": [2]}, true]),
+                json!([1, "a", {"b": [2]}, true]),
+            );
+        }
+
+        #[test]
+        fn nulls_are_kept_unless_projected() {
+            assert_flattens(json!([null, [null, 1]]), json!([null, null, 1]));
+            assert_eq!(json!([null, [null, 1]]).flatten_project(|v| v), json!([1]));
+        }
+
+        #[test]
+        fn non_arrays_are_null() {
+            for value in [json!({"a": [1]}), json!("[1]"), json!(1), Null] {
+                assert_flattens(value.clone(), Null);
+                assert_eq!(value.flatten_project(|v| v), Null);
+            }
+        }
+    }
+
     fn objects_in_nested_list() -> Value {
         json!([
             {"name": "Seattle", "state": "WA"},
@@ -462,6 +2674,580 @@ mod tests {
         )
     }
 
+    #[test]
+    fn leaves() {
+        assert_eq!(complex().leaves(), json!([0, 1, 2, 3, 4]));
+        assert_eq!(json!("scalar").leaves(), json!(["scalar"]));
+    }
+
+    #[test]
+    fn paths() {
+        let rendered = complex()
+            .paths()
+            .map(|path| render_path(&path))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rendered,
+            [
+                "a.b.c[0].d[0]",
+                "a.b.c[0].d[1][0]",
+                "a.b.c[0].d[1][1]",
+                "a.b.c[1].d[0]",
+                "a.b.c[1].d[1]"
+            ]
+        );
+        for path in complex().paths() {
+            assert!(complex().get_path(&path).is_ok());
+        }
+        assert_eq!(json!("scalar").paths().collect::<Vec<_>>(), [vec![]]);
+        assert_eq!(json!({"a": [], "b": {}}).paths().count(), 0);
+
+        let mut deep = json!(0);
+        for _ in 0..100_000 {
+            deep = Value::Array(vec![deep]);
+        }
+        assert_eq!(deep.paths().next().map(|path| path.len()), Some(100_000));
+        // Dropping is recursive, so leak rather than overflow the stack
+        mem::forget(deep);
+    }
+
+    #[test]
+    fn sort() {
+        assert_eq!(json!([1, 2.5, 0]).sort(), json!([0, 1, 2.5]));
+        assert_eq!(json!(["b", "c", "a"]).sort(), json!(["a", "b", "c"]));
+        assert_eq!(json!([]).sort(), json!([]));
+        assert_eq!(json!([1, "a"]).sort(), json!(null));
+        assert_eq!(json!([true, false]).sort(), json!(null));
+        assert_eq!(flatmap().sort(), json!(null));
+    }
+
+    #[test]
+    fn canonical_string_sorts_keys() {
+        let forwards =
+            serde_json::from_str(r#"{"a": 1, "b": {"c": [true, null], "d": "e"}}"#).unwrap();
+        let backwards =
+            serde_json::from_str(r#"{"b": {"d": "e", "c": [true, null]}, "a": 1}"#).unwrap();
+        assert_eq!(canonical_string(&forwards), canonical_string(&backwards));
+        assert_eq!(
+            canonical_string(&forwards),
+            r#"{"a":1,"b":{"c":[true,null],"d":"e"}}"#
+        );
+        assert_ne!(
+            canonical_string(&json!([1, 2])),
+            canonical_string(&json!([2, 1]))
+        );
+    }
+
+    #[test]
+    fn canonical_string_agrees_with_semantically_eq() {
+        assert_eq!(
+            canonical_string(&json!({"a": 1.0})),
+            canonical_string(&json!({"a": 1}))
+        );
+        assert_eq!(canonical_string(&json!(-0.0)), canonical_string(&json!(0)));
+        assert_eq!(canonical_string(&json!(1.5)), "1.5");
+        assert_eq!(canonical_string(&json!(u64::MAX)), u64::MAX.to_string());
+        assert_ne!(canonical_string(&json!(1)), canonical_string(&json!("1")));
+    }
+
+    #[test]
+    fn slice_str_expr() {
+        assert_eq!(array().slice_str_expr("::2"), json!(["a", "c", "e"]));
+        assert_eq!(array().slice_str_expr("-2:"), json!(["e", "f"]));
+        assert_eq!(array().slice_str_expr("::0"), Null);
+        assert_eq!(array().slice_str_expr("nonsense"), Null);
+    }
+
+    #[test]
+    fn slice_inclusive() {
+        assert_eq!(
+            array().slice_inclusive(Some(0), Some(2), None),
+            json!(["a", "b", "c"])
+        );
+        assert_eq!(
+            array().slice_inclusive(Some(4), Some(-1), None),
+            json!(["e", "f"])
+        );
+        assert_eq!(
+            array().slice_inclusive(None, Some(-2), Some(2)),
+            json!(["a", "c", "e"])
+        );
+        assert_eq!(
+            array().slice_inclusive(Some(2), Some(0), Some(-1)),
+            json!(["c", "b", "a"])
+        );
+        assert_eq!(
+            array().slice_inclusive(None, Some(1), Some(-2)),
+            json!(["f", "d", "b"])
+        );
+        assert_eq!(
+            array().slice_inclusive(Some(1), Some(isize::MAX), None),
+            array().slice(1..)
+        );
+        assert_eq!(array().slice_inclusive(None, None, Some(0)), Null);
+    }
+
+    #[test]
+    fn slice_enumerated() {
+        assert_eq!(
+            array().slice_enumerated("::2".parse::<JMESSlice>().unwrap()),
+            json!([[0, "a"], [2, "c"], [4, "e"]])
+        );
+        assert_eq!(
+            array().slice_enumerated("-2::-3".parse::<JMESSlice>().unwrap()),
+            json!([[4, "e"], [1, "b"]])
+        );
+        assert_eq!(flatmap().slice_enumerated(..1), Null);
+    }
+
+    #[test]
+    fn slice_bytes() {
+        assert_eq!(json!("hello").slice_bytes(1..3), json!("el"));
+        assert_eq!(
+            json!("hello").slice_bytes(JMESSlice::reversed()),
+            json!("olleh")
+        );
+        // `é` is two bytes
+        assert_eq!(json!("café").slice_bytes(..4), Null);
+        assert_eq!(json!("café").slice_bytes(..5), json!("café"));
+        assert_eq!(json!(["a"]).slice_bytes(..1), Null);
+    }
+
+    #[test]
+    fn replace_at_path() {
+        use PathSegment::{Key, Slice};
+        let path = "a.b.c[0].d[1]"
+            .parse::<Expression>()
+            .unwrap()
+            .to_path()
+            .unwrap();
+        assert_eq!(
+            complex().replace_at_path(&path, json!("new")),
+            json!({"a": {"b": {"c": [
+                {"d": [0, "new"]},
+                {"d": [3, 4]}
+            ]}}})
+        );
+        // Nothing is created
+        let missing = [Key("a".into()), Key("x".into()), Key("y".into())];
+        assert_eq!(complex().replace_at_path(&missing, json!(1)), complex());
+        let sliced = [Key("a".into()), Slice(JMESSlice::default())];
+        assert_eq!(complex().replace_at_path(&sliced, json!(1)), complex());
+        assert_eq!(complex().replace_at_path(&[], json!(1)), json!(1));
+    }
+
+    #[test]
+    fn take_path() {
+        use PathSegment::{Index, Key, Slice};
+        let path = |s: &str| s.parse::<Expression>().unwrap().to_path().unwrap();
+        let mut document = complex();
+        assert_eq!(
+            document.take_path(&path("a.b.c")),
+            json!([{"d": [0, [1, 2]]}, {"d": [3, 4]}])
+        );
+        assert_eq!(document, json!({"a": {"b": {}}}));
+        assert_eq!(document.take_path(&path("a.b.c")), Null);
+
+        let mut document = complex();
+        assert_eq!(document.take_path(&path("a.b.c[0].d[-1]")), json!([1, 2]));
+        assert_eq!(document.take_path(&path("a.b.c[1]")), json!({"d": [3, 4]}));
+        assert_eq!(
+            document,
+            json!({"a": {"b": {"c": [{"d": [0, null]}, null]}}})
+        );
+        assert_eq!(document.take_path(&path("a.b.c[2]")), Null);
+        assert_eq!(document.take_path(&path("a.b.c[0].d[0].e")), Null);
+        let sliced = [Key("a".into()), Slice(JMESSlice::default()), Index(0)];
+        assert_eq!(document.take_path(&sliced), Null);
+
+        assert_eq!(
+            document.take_path(&[]),
+            json!({"a": {"b": {"c": [{"d": [0, null]}, null]}}})
+        );
+        assert_eq!(document, Null);
+    }
+
+    #[test]
+    fn require_paths() {
+        use PathSegment::{Index, Key};
+        let document = json!({"name": "x", "tags": ["a"], "owner": {"id": null}});
+        let name = [Key("name".into())];
+        let first_tag = [Key("tags".into()), Index(0)];
+        let owner_id = [Key("owner".into()), Key("id".into())];
+        assert_eq!(
+            document.require_paths(&[&name, &first_tag, &owner_id]),
+            Err(vec!["owner.id".into()])
+        );
+        assert_eq!(document.require_paths(&[&name, &first_tag]), Ok(()));
+        assert_eq!(json!(null).require_paths(&[&[]]), Err(vec!["@".into()]));
+    }
+
+    #[test]
+    fn identify_projecting() {
+        assert_eq!(
+            list_project_example()
+                .identify("people")
+                .identify_projecting("first"),
+            json!(["James", "Jacob", "Jayden"])
+        );
+        assert_eq!(
+            list_project_example()
+                .identify("foo")
+                .identify_projecting("bar"),
+            json!("baz")
+        );
+        assert_eq!(json!("a").identify_projecting("bar"), Null);
+    }
+
+    #[test]
+    fn estimated_json_len() {
+        for value in [
+            complex(),
+            list_project_example(),
+            json!([
+                null,
+                true,
+                false,
+                0,
+                9,
+                10,
+                -1,
+                -10,
+                u64::MAX,
+                i64::MIN,
+                1.5,
+                "",
+                {},
+                []
+            ]),
+        ] {
+            assert_eq!(
+                super::estimated_json_len(&value),
+                serde_json::to_string(&value).unwrap().len(),
+                "{}",
+                value
+            );
+        }
+        let escaped = json!({"a": "\"quoted\"\n"});
+        let actual = serde_json::to_string(&escaped).unwrap().len();
+        let estimate = super::estimated_json_len(&escaped);
+        assert!(estimate <= actual && actual < estimate * 2);
+    }
+
+    #[test]
+    fn object() {
+        assert_eq!(
+            super::object([("a", json!(1)), ("b", json!(2))]),
+            json!({"a": 1, "b": 2})
+        );
+        assert_eq!(
+            super::object(vec![(String::from("a"), json!(1)), ("a".into(), json!(2))]),
+            json!({"a": 2})
+        );
+    }
+
+    #[test]
+    fn diff() {
+        let before = json!({"a": {"b": [1, {"weird key": 2}]}, "c": 3});
+        let after = json!({"a": {"b": [1, {"weird key": 4}]}, "c": 3, "d": 5});
+        assert_eq!(
+            super::diff(&before, &after),
+            vec![
+                (r#"a.b[1]."weird key""#.into(), json!(2), json!(4)),
+                ("d".into(), Null, json!(5)),
+            ]
+        );
+        assert_eq!(super::diff(&before, &before), vec![]);
+        assert_eq!(
+            super::diff(&json!(1), &json!([1])),
+            vec![("@".into(), json!(1), json!([1]))]
+        );
+        // Added and removed nulls are still reported, in either direction
+        assert_eq!(
+            super::diff(&json!({"a": null}), &json!({})),
+            vec![("a".into(), Null, Null)]
+        );
+        assert_eq!(
+            super::diff(&json!({}), &json!({"a": null})),
+            vec![("a".into(), Null, Null)]
+        );
+        assert_eq!(
+            super::diff(&json!([null]), &json!([])),
+            vec![("[0]".into(), Null, Null)]
+        );
+        assert_eq!(
+            super::diff(&json!([]), &json!([null])),
+            vec![("[0]".into(), Null, Null)]
+        );
+        // And whole
+        assert_eq!(
+            super::diff(&json!({"a": {"b": 1}}), &json!({})),
+            vec![("a".into(), json!({"b": 1}), Null)]
+        );
+        assert_eq!(
+            super::diff(&json!([1]), &json!([1, [2]])),
+            vec![("[1]".into(), Null, json!([2]))]
+        );
+    }
+
+    #[test]
+    fn semantically_eq() {
+        assert!(super::semantically_eq(&json!({"a": 1.0}), &json!({"a": 1})));
+        assert!(super::semantically_eq(
+            &json!([{"b": [2, 3.0]}, -0.0]),
+            &json!([{"b": [2.0, 3]}, 0])
+        ));
+        assert!(!super::semantically_eq(
+            &json!({"a": 1}),
+            &json!({"a": 1, "b": 1})
+        ));
+        assert!(!super::semantically_eq(&json!([1]), &json!([1, 1])));
+        assert!(!super::semantically_eq(&json!(1), &json!("1")));
+        // Beyond f64's precision
+        assert!(!super::semantically_eq(
+            &json!(9007199254740993u64),
+            &json!(9007199254740992u64)
+        ));
+        assert_eq!(
+            super::diff(&json!({"a": [1]}), &json!({"a": [1.0]})),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn eq_ignoring() {
+        let path = |s: &str| s.parse::<Expression>().unwrap().to_path().unwrap();
+        let timestamp = path("meta.timestamp");
+        let before = json!({"name": "a", "meta": {"timestamp": 1, "v": 2}});
+        let after = json!({"name": "a", "meta": {"timestamp": 5, "v": 2}});
+        assert!(super::eq_ignoring(&before, &after, &[&timestamp]));
+        assert!(!super::eq_ignoring(&before, &after, &[]));
+        // Missing on one side is ignored too
+        let missing = json!({"name": "a", "meta": {"v": 2.0}});
+        assert!(super::eq_ignoring(&before, &missing, &[&timestamp]));
+        let changed = json!({"name": "b", "meta": {"timestamp": 1, "v": 2}});
+        assert!(!super::eq_ignoring(&before, &changed, &[&timestamp]));
+
+        let ids = path("items[:].id");
+        let before = json!({"items": [{"id": 1, "n": "x"}, {"id": 2, "n": "y"}]});
+        let after = json!({"items": [{"id": 7, "n": "x"}, {"id": 8, "n": "y"}]});
+        assert!(super::eq_ignoring(&before, &after, &[&ids]));
+        let first = path("items[0].id");
+        assert!(!super::eq_ignoring(&before, &after, &[&first]));
+        let evens = path("items[::2].id");
+        assert!(!super::eq_ignoring(&before, &after, &[&evens]));
+        assert!(super::eq_ignoring(
+            &before,
+            &after,
+            &[&evens, &path("items[1].id")]
+        ));
+        assert!(super::eq_ignoring(&json!(1), &json!(2), &[&[]]));
+    }
+
+    #[test]
+    fn reshape() {
+        let flat = |n: i64| super::range(1, n + 1, 1).as_array().unwrap().clone();
+        assert_eq!(super::reshape(flat(4), &[2, 2]), json!([[1, 2], [3, 4]]));
+        assert_eq!(
+            super::reshape(flat(6), &[3, 2]),
+            json!([[1, 2], [3, 4], [5, 6]])
+        );
+        assert_eq!(
+            super::reshape(flat(6), &[1, 2, 3]),
+            json!([[[1, 2, 3], [4, 5, 6]]])
+        );
+        assert_eq!(super::reshape(flat(4), &[4]), json!([1, 2, 3, 4]));
+        assert_eq!(super::reshape(flat(4), &[3]), Null);
+        assert_eq!(super::reshape(flat(4), &[2, 3]), Null);
+        assert_eq!(super::reshape(flat(1), &[]), json!(1));
+        assert_eq!(super::reshape(vec![], &[1, 0]), json!([[]]));
+        assert_eq!(super::reshape(vec![], &[0, 2]), json!([]));
+        assert_eq!(super::reshape(vec![], &[usize::MAX, 2, 0]), Null);
+        assert_eq!(super::reshape(vec![], &[usize::MAX, 0]), Null);
+        assert_eq!(super::reshape(vec![], &[1 << 40, 0]), Null);
+        assert_eq!(super::reshape(vec![], &[2, 0]), Null);
+        // The inverse of flattening
+        let nested = json!([[1, 2], [3, 4]]);
+        assert_eq!(
+            super::reshape(
+                nested.clone().flatten_n(1).as_array().unwrap().clone(),
+                &[2, 2]
+            ),
+            nested
+        );
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(super::range(0, 5, 2), json!([0, 2, 4]));
+        assert_eq!(super::range(5, 0, -1), json!([5, 4, 3, 2, 1]));
+        assert_eq!(super::range(0, 5, -1), json!([]));
+        assert_eq!(super::range(3, 3, 1), json!([]));
+        assert_eq!(super::range(0, 5, 0), Null);
+        assert_eq!(
+            super::range(i64::MAX - 1, i64::MAX, 5),
+            json!([i64::MAX - 1])
+        );
+    }
+
+    #[test]
+    fn structural_hash() {
+        use super::structural_hash as hash;
+        assert_eq!(hash(&json!({"a": 1})), hash(&json!({"a": 1.0})));
+        assert_eq!(hash(&json!([0, -0.0])), hash(&json!([0.0, 0])));
+        assert_ne!(hash(&json!({"a": 1})), hash(&json!({"a": 2})));
+        assert_ne!(hash(&json!({"a": 1})), hash(&json!({"b": 1})));
+        assert_ne!(hash(&json!(["ab", "c"])), hash(&json!(["a", "bc"])));
+        assert_ne!(hash(&json!([[1], 2])), hash(&json!([1, [2]])));
+        assert_ne!(hash(&json!(1)), hash(&json!("1")));
+        assert_ne!(hash(&Null), hash(&json!(false)));
+        // Fixed, so it can be persisted
+        assert_eq!(hash(&Null), 0xaf63_bd4c_8601_b7df);
+    }
+
+    #[test]
+    fn flatten_n() {
+        assert_eq!(json!([[[1]]]).flatten_n(0), json!([[[1]]]));
+        assert_eq!(json!([[[1]]]).flatten_n(1), json!([[1]]));
+        assert_eq!(json!([[[1]]]).flatten_n(2), json!([1]));
+        assert_eq!(json!([[[1]], 2]).flatten_n(usize::MAX), json!([1, 2]));
+        assert_eq!(flatmap().flatten_n(1), Null);
+    }
+
+    #[test]
+    fn slyce_index() {
+        use slyce::Index;
+        assert_eq!(super::slyce_index(None), Index::Default);
+        assert_eq!(super::slyce_index(Some(2)), Index::Head(2));
+        assert_eq!(super::slyce_index(Some(-2)), Index::Tail(2));
+        assert_eq!(
+            super::slyce_index(Some(isize::MIN)),
+            Index::Tail(isize::MIN.unsigned_abs())
+        );
+    }
+
+    #[test]
+    fn truncate_strings() {
+        let document =
+            json!({"a": ["short", "much too long"], "b": {"c": "héllo wörld", "d": 12345678}});
+        assert_eq!(
+            document.clone().truncate_strings(5, false),
+            json!({"a": ["short", "much "], "b": {"c": "héllo", "d": 12345678}})
+        );
+        assert_eq!(
+            document.truncate_strings(5, true),
+            json!({"a": ["short", "much…"], "b": {"c": "héll…", "d": 12345678}})
+        );
+        assert_eq!(json!("abc").truncate_strings(0, true), json!(""));
+    }
+
+    #[test]
+    fn normalize_numbers() {
+        assert_eq!(
+            json!({"a": 2.0, "b": [1.0, 1.5]})
+                .normalize_numbers()
+                .to_string(),
+            r#"{"a":2,"b":[1,1.5]}"#
+        );
+        assert_eq!(json!(-3.0).normalize_numbers().as_i64(), Some(-3));
+        assert_eq!(
+            json!(1e19).normalize_numbers().as_u64(),
+            Some(10_000_000_000_000_000_000)
+        );
+        assert_eq!(
+            json!(2f64.powi(63)).normalize_numbers().as_u64(),
+            Some(1 << 63)
+        );
+        // Too big for either
+        assert!(json!(1e300).normalize_numbers().is_f64());
+        assert_eq!(json!(u64::MAX).normalize_numbers(), json!(u64::MAX));
+        assert_eq!(json!("2.0").normalize_numbers(), json!("2.0"));
+    }
+
+    #[test]
+    fn redact() {
+        let document = json!({
+            "user": {"name": "a", "Password": "hunter2", "tokens": [{"token": {"id": 1}}]},
+            "password_hint": "b"
+        });
+        assert_eq!(
+            document.redact(&["password", "token"], json!("***")),
+            json!({
+                "user": {"name": "a", "Password": "***", "tokens": [{"token": "***"}]},
+                "password_hint": "b"
+            })
+        );
+        assert_eq!(array().redact(&["a"], Null), array());
+    }
+
+    #[test]
+    fn between() {
+        assert_eq!(
+            json!([1, 5, 10, 15]).between(&json!(5), &json!(12)),
+            json!([5, 10])
+        );
+        assert_eq!(
+            json!([0.5, "1", 1, 2.5, null]).between(&json!(0), &json!(2.5)),
+            json!([0.5, 1, 2.5])
+        );
+        assert_eq!(json!([1]).between(&json!(2), &json!(0)), json!([]));
+        assert_eq!(json!([1]).between(&json!("a"), &json!(2)), Null);
+        assert_eq!(flatmap().between(&json!(0), &json!(2)), Null);
+    }
+
+    #[test]
+    fn sort_by() {
+        // The last person has no `first`
+        let people = list_project_example().identify("people").slice(..3);
+        let first = |person: &Value| person["first"].clone();
+        assert_eq!(
+            people
+                .clone()
+                .sort_by_desc(first)
+                .list_project(|p| p.identify("first")),
+            json!(["Jayden", "James", "Jacob"])
+        );
+        assert_eq!(
+            people.sort_by(first).list_project(|p| p.identify("first")),
+            json!(["Jacob", "James", "Jayden"])
+        );
+        let ties = json!([{"k": 1, "v": "a"}, {"k": 2, "v": "b"}, {"k": 1, "v": "c"}]);
+        let k = |value: &Value| value["k"].clone();
+        assert_eq!(
+            ties.clone()
+                .sort_by_desc(k)
+                .list_project(|p| p.identify("v")),
+            json!(["b", "a", "c"])
+        );
+        assert_eq!(
+            ties.sort_by(k).list_project(|p| p.identify("v")),
+            json!(["a", "c", "b"])
+        );
+        assert_eq!(
+            list_project_example().identify("people").sort_by(first),
+            Null
+        );
+    }
+
+    #[test]
+    fn try_sort() {
+        assert_eq!(json!([2, 1]).try_sort(), Ok(json!([1, 2])));
+        assert_eq!(json!([]).try_sort(), Ok(json!([])));
+        assert_eq!(
+            json!([1, "a"]).try_sort(),
+            Err(SortError::Heterogeneous {
+                index: 1,
+                expected: "number",
+                found: "string"
+            })
+        );
+        assert_eq!(
+            json!([true, false]).try_sort(),
+            Err(SortError::Unsortable("boolean"))
+        );
+        assert_eq!(flatmap().try_sort(), Err(SortError::NotArray("object")));
+    }
+
     #[test]
     fn running() {
         let program = JMESProgram::new("hello.world").unwrap();
@@ -0,0 +1,72 @@
+//! [`JMESPath`](crate::JMESPath) methods which build objects, collecting into an [`IndexMap`] instead.
+//! Keys are in the order they were first inserted.
+
+use crate::HashEntry;
+pub use indexmap::IndexMap;
+use serde_json::Value;
+
+/// [`JMESPath::multiselect_hash`](crate::JMESPath::multiselect_hash), or `None` where that is `null`.
+pub fn multiselect_hash(value: Value, entries: &[HashEntry]) -> Option<IndexMap<String, Value>> {
+    crate::multiselect_hash(value, entries)
+}
+
+/// [`JMESPath::group_by`](crate::JMESPath::group_by), or `None` where that is `null`.
+/// Groups are in the order their keys are first seen.
+pub fn group_by(value: Value, key: impl Fn(&Value) -> Value) -> Option<IndexMap<String, Value>> {
+    crate::group_by(value, key)
+}
+
+/// [`JMESPath::map_keys`](crate::JMESPath::map_keys), or `None` where that is `null`.
+pub fn map_keys(value: Value, f: impl Fn(&str) -> String) -> Option<IndexMap<String, Value>> {
+    crate::map_keys(value, f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JMESPath;
+    use serde_json::json;
+
+    #[test]
+    fn group_by_first_seen() {
+        let items = json!([
+            {"kind": "z", "n": 1},
+            {"kind": "a", "n": 2},
+            {"kind": "z", "n": 3},
+            {"kind": "m", "n": 4}
+        ]);
+        let groups = group_by(items, |item| item["kind"].clone()).unwrap();
+        assert_eq!(groups.keys().collect::<Vec<_>>(), ["z", "a", "m"]);
+        assert_eq!(
+            groups["z"],
+            json!([{"kind": "z", "n": 1}, {"kind": "z", "n": 3}])
+        );
+    }
+
+    #[test]
+    fn multiselect_hash_and_map_keys_keep_order() {
+        let hash = multiselect_hash(
+            json!({"a": 1}),
+            &[
+                HashEntry::Named("z", &|v| v.identify("a")),
+                HashEntry::Named("b", &|v| v),
+                HashEntry::Named("z", &|_| json!(2)),
+            ],
+        )
+        .unwrap();
+        // Overriding keeps the original position
+        assert_eq!(
+            hash.into_iter().collect::<Vec<_>>(),
+            [
+                ("z".to_string(), json!(2)),
+                ("b".to_string(), json!({"a": 1}))
+            ]
+        );
+        let keys = map_keys(json!({"a": 1, "b": 2}), |key| {
+            format!("{}{}", 9 - key.len(), key)
+        })
+        .unwrap();
+        assert_eq!(keys.keys().collect::<Vec<_>>(), ["8a", "8b"]);
+        assert_eq!(map_keys(json!([]), str::to_owned), None);
+    }
+}
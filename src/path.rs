@@ -0,0 +1,270 @@
+//! Paths to a node within a document, as a list of steps.
+
+use crate::{normalize_index, type_name, Expression, JMESPath, JMESSlice};
+use serde_json::Value::{self, Array, Null, Object};
+use std::{borrow::Cow, fmt::Write as _};
+use thiserror::Error;
+
+/// One step of a path.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum PathSegment {
+    /// `.key`
+    Key(String),
+    /// `[0]`
+    Index(isize),
+    /// `[start:end:step]`
+    Slice(JMESSlice),
+}
+
+/// Why [`JMESPath::get_path`] couldn't follow a path.
+/// Each carries the rendered path which was walked before the failing segment.
+#[derive(Debug, Error, PartialEq, Eq, Hash, Clone)]
+pub enum PathError {
+    #[error("Expected an object at {path}, but found {found}")]
+    NotAnObject { path: String, found: &'static str },
+    #[error("Expected an array at {path}, but found {found}")]
+    NotAnArray { path: String, found: &'static str },
+    #[error("No key {key:?} at {path}")]
+    MissingKey { path: String, key: String },
+    #[error("Index {index} out of bounds at {path}")]
+    OutOfBounds { path: String, index: isize },
+}
+
+/// See [`JMESPath::get_path`].
+pub(crate) fn get_path(mut value: Value, path: &[PathSegment]) -> Result<Value, PathError> {
+    for (i, segment) in path.iter().enumerate() {
+        let walked = || render_path(&path[..i]);
+        value = match (segment, value) {
+            (PathSegment::Key(key), Object(mut map)) => {
+                map.remove(key).ok_or_else(|| PathError::MissingKey {
+                    path: walked(),
+                    key: key.clone(),
+                })?
+            }
+            (PathSegment::Index(index), Array(mut vec)) => {
+                match normalize_index(*index, vec.len()) {
+                    Some(index) => vec.swap_remove(index),
+                    None => {
+                        return Err(PathError::OutOfBounds {
+                            path: walked(),
+                            index: *index,
+                        })
+                    }
+                }
+            }
+            (PathSegment::Slice(slice), array @ Array(_)) => {
+                // Like a slice projection, elements that don't have the rest of the path are dropped
+                let rest = &path[i + 1..];
+                return Ok(array
+                    .slice(*slice)
+                    .list_project(|element| get_path(element, rest).unwrap_or(Null)));
+            }
+            (PathSegment::Key(_), other) => {
+                return Err(PathError::NotAnObject {
+                    path: walked(),
+                    found: type_name(&other),
+                })
+            }
+            (PathSegment::Index(_) | PathSegment::Slice(_), other) => {
+                return Err(PathError::NotAnArray {
+                    path: walked(),
+                    found: type_name(&other),
+                })
+            }
+        };
+    }
+    Ok(value)
+}
+
+/// [`get_path`] by reference, which only needs to clone for slices, as they project the rest of the path.
+pub(crate) fn lookup<'a>(mut value: &'a Value, path: &[PathSegment]) -> Option<Cow<'a, Value>> {
+    for (i, segment) in path.iter().enumerate() {
+        value = match (segment, value) {
+            (PathSegment::Key(key), Object(map)) => map.get(key)?,
+            (PathSegment::Index(index), Array(vec)) => &vec[normalize_index(*index, vec.len())?],
+            (PathSegment::Slice(_), Array(_)) => {
+                return get_path(value.clone(), &path[i..]).ok().map(Cow::Owned)
+            }
+            _ => return None,
+        }
+    }
+    Some(Cow::Borrowed(value))
+}
+
+/// The node at `path`, if it exists, which it never does if there are slices, as those aren't a single node.
+pub(crate) fn lookup_mut<'a>(
+    mut value: &'a mut Value,
+    path: &[PathSegment],
+) -> Option<&'a mut Value> {
+    for segment in path {
+        value = match (segment, value) {
+            (PathSegment::Key(key), Object(map)) => map.get_mut(key)?,
+            (PathSegment::Index(index), Array(vec)) => {
+                let index = normalize_index(*index, vec.len())?;
+                &mut vec[index]
+            }
+            _ => return None,
+        }
+    }
+    Some(value)
+}
+
+/// Remove every node at `path`, like [`JMESPath::take_path`], but through slices too,
+/// which remove the rest of the path from each element they select.
+pub(crate) fn remove_all(value: &mut Value, path: &[PathSegment]) {
+    let mut stack = vec![(value, path)];
+    while let Some((value, path)) = stack.pop() {
+        match (path, value) {
+            ([], value) => *value = Null,
+            ([PathSegment::Key(key)], Object(map)) => {
+                map.remove(key);
+            }
+            ([PathSegment::Key(key), rest @ ..], Object(map)) => {
+                stack.extend(map.get_mut(key).map(|value| (value, rest)))
+            }
+            ([PathSegment::Index(index), rest @ ..], Array(vec)) => {
+                if let Some(index) = normalize_index(*index, vec.len()) {
+                    stack.push((&mut vec[index], rest))
+                }
+            }
+            ([PathSegment::Slice(slice), rest @ ..], Array(vec)) => {
+                let mut selected = vec![false; vec.len()];
+                for index in slice.indices(vec.len()) {
+                    selected[index] = true
+                }
+                stack.extend(
+                    vec.iter_mut()
+                        .zip(selected)
+                        .filter(|(_, selected)| *selected)
+                        .map(|(value, _)| (value, rest)),
+                )
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The canonical JMESPath expression for `path`, or `@` if it is empty.
+///
+/// Keys are quoted where the grammar requires, and parse back with [`Expression::to_path`].
+pub fn render_path(path: &[PathSegment]) -> String {
+    let mut out = String::new();
+    for segment in path {
+        match segment {
+            PathSegment::Key(key) => {
+                if !out.is_empty() {
+                    out.push('.')
+                }
+                write!(out, "{}", Expression::Identifier(key.clone()))
+            }
+            PathSegment::Index(index) => write!(out, "[{}]", index),
+            PathSegment::Slice(slice) => write!(out, "[{}]", slice),
+        }
+        .expect("Writing to a String is infallible")
+    }
+    if out.is_empty() {
+        out.push('@')
+    }
+    out
+}
+
+impl Expression {
+    /// The path this expression follows, if it is only identifiers, indices and slices.
+    pub fn to_path(&self) -> Option<Vec<PathSegment>> {
+        let mut path = Vec::new();
+        self.push_path(&mut path).then_some(path)
+    }
+
+    fn push_path(&self, path: &mut Vec<PathSegment>) -> bool {
+        match self {
+            Expression::Current => true,
+            Expression::Identifier(key) => {
+                path.push(PathSegment::Key(key.clone()));
+                true
+            }
+            Expression::Index(index) => {
+                path.push(PathSegment::Index(*index));
+                true
+            }
+            Expression::Subexpression(lhs, rhs) => lhs.push_path(path) && rhs.push_path(path),
+            Expression::SliceProjection {
+                lhs, slice, rhs, ..
+            } => {
+                if !lhs.push_path(path) {
+                    return false;
+                }
+                path.push(PathSegment::Slice(*slice));
+                rhs.push_path(path)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use PathSegment::{Index, Key, Slice};
+
+    #[test]
+    fn get_path() {
+        let data = json!({"a": {"b": {"c": [{"d": 1}, {"d": 2}, {}]}}});
+        let path = |s: &str| s.parse::<Expression>().unwrap().to_path().unwrap();
+        assert_eq!(data.clone().get_path(&path("a.b.c[1].d")), Ok(json!(2)));
+        assert_eq!(
+            data.clone().get_path(&path("a.b.c[:].d")),
+            Ok(json!([1, 2]))
+        );
+        assert_eq!(
+            data.clone().get_path(&path("a.b.x")),
+            Err(PathError::MissingKey {
+                path: "a.b".into(),
+                key: "x".into()
+            })
+        );
+        assert_eq!(
+            data.clone().get_path(&path("a.b.c[3]")),
+            Err(PathError::OutOfBounds {
+                path: "a.b.c".into(),
+                index: 3
+            })
+        );
+        assert_eq!(
+            data.clone().get_path(&path("a[0]")),
+            Err(PathError::NotAnArray {
+                path: "a".into(),
+                found: "object"
+            })
+        );
+        assert_eq!(
+            json!([]).try_identify("a"),
+            Err(PathError::NotAnObject {
+                path: "@".into(),
+                found: "array"
+            })
+        );
+    }
+
+    #[test]
+    fn render_path() {
+        let path = [Key("a".into()), Index(0), Key("weird key".into())];
+        let rendered = super::render_path(&path);
+        assert_eq!(rendered, r#"a[0]."weird key""#);
+        assert_eq!(
+            rendered.parse::<Expression>().unwrap().to_path(),
+            Some(path.to_vec())
+        );
+
+        let path = [Index(-1), Slice(JMESSlice::reversed()), Key("b".into())];
+        let rendered = super::render_path(&path);
+        assert_eq!(rendered, "[-1][::-1].b");
+        assert_eq!(
+            rendered.parse::<Expression>().unwrap().to_path(),
+            Some(path.to_vec())
+        );
+
+        assert_eq!(super::render_path(&[]), "@");
+        assert_eq!("a || b".parse::<Expression>().unwrap().to_path(), None);
+    }
+}
@@ -0,0 +1,1433 @@
+//! Evaluating [`Expression`]s against documents.
+
+use crate::{
+    ast::{Comparator, Expression, SliceCache},
+    compare_numbers,
+    functions::{self, Argument},
+    lenient_identify, lenient_index, normalize_index, semantically_eq, JMESSlice,
+    ParseExpressionError,
+};
+use serde_json::{
+    Map,
+    Value::{self, Array, Bool, Null, Number, Object},
+};
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    io, mem,
+};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq, Clone)]
+pub enum SearchError {
+    #[error("Couldn't parse expression: {0}")]
+    Parse(#[from] ParseExpressionError),
+    #[error("Unbound variable ${0}")]
+    UnboundVariable(String),
+    #[error("Search exceeded its limits")]
+    LimitExceeded,
+    #[error("Unknown function {0}()")]
+    UnknownFunction(String),
+    #[error("{function}() takes {expected} arguments, but was given {found}")]
+    WrongArgumentCount {
+        function: String,
+        /// The minimum, for variadic functions
+        expected: usize,
+        found: usize,
+    },
+    #[error("Couldn't write results: {0}")]
+    Write(String),
+}
+
+/// Limits on evaluation, for when expressions are untrusted.
+/// The default is no limits.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct SearchOptions {
+    /// How deeply subexpressions may nest.
+    pub max_depth: Option<usize>,
+    /// How many times subexpressions may be evaluated in total, including once per projected element.
+    pub max_steps: Option<usize>,
+}
+
+/// Parse and evaluate `expression` against `data`.
+pub fn search(expression: &str, data: Value) -> Result<Value, SearchError> {
+    search_with(expression, data, &Map::new())
+}
+
+/// Like [`search`], resolving `$name` variables from `vars`.
+pub fn search_with(
+    expression: &str,
+    data: Value,
+    vars: &Map<String, Value>,
+) -> Result<Value, SearchError> {
+    expression.parse::<Expression>()?.search_with(data, vars)
+}
+
+/// Evaluate each of `expressions` against `data`, without cloning all of `data` for each.
+/// See [`Expression::search_ref`].
+pub fn search_many(expressions: &[&str], data: &Value) -> Vec<Result<Value, SearchError>> {
+    expressions
+        .iter()
+        .map(|expression| expression.parse::<Expression>()?.search_ref(data))
+        .collect()
+}
+
+/// Like [`search_with`], failing with [`SearchError::LimitExceeded`] if evaluation goes past `options`.
+pub fn search_with_options(
+    expression: &str,
+    data: Value,
+    vars: &Map<String, Value>,
+    options: SearchOptions,
+) -> Result<Value, SearchError> {
+    expression
+        .parse::<Expression>()?
+        .search_with_options(data, vars, options)
+}
+
+/// For autocompletion, evaluate the longest prefix of `expression` which parses,
+/// so a trailing incomplete segment like the `.` of `a.b.` is ignored.
+///
+/// Returns the value reached, and its keys if it is an object, to suggest what might come next.
+/// An empty `expression` is `@`.
+pub fn evaluate_prefix(
+    expression: &str,
+    data: &Value,
+) -> Result<(Value, Vec<String>), SearchError> {
+    let expression = expression.trim_end();
+    let parsed = match expression.parse::<Expression>() {
+        _ if expression.is_empty() => Expression::Current,
+        Ok(parsed) => parsed,
+        Err(error) => expression
+            .char_indices()
+            .rev()
+            .find_map(|(end, _)| expression[..end].parse().ok())
+            .ok_or(error)?,
+    };
+    let value = parsed.search_ref(data)?;
+    let keys = match &value {
+        Object(map) => map.keys().cloned().collect(),
+        _ => Vec::new(),
+    };
+    Ok((value, keys))
+}
+
+/// For debugging, each step along `expression` and what it evaluates to against `data`,
+/// so you can see where a projection collapsed.
+///
+/// Steps are the prefixes of the expression, like `a[:]`, `a[:].b`, then `a[:].b[0]`, each evaluated from scratch.
+pub fn trace(expression: &Expression, data: Value) -> Result<Vec<(String, Value)>, SearchError> {
+    let mut prefixes = expression.prefixes();
+    // Projections elide `@`, so it isn't a step a reader would recognise
+    if prefixes.len() > 1 {
+        prefixes.retain(|prefix| *prefix != Expression::Current)
+    }
+    prefixes
+        .into_iter()
+        .map(|prefix| Ok((prefix.to_string(), prefix.search(data.clone())?)))
+        .collect()
+}
+
+/// Evaluate `expression` against `data`, serializing the result to `writer` as compact JSON.
+///
+/// If `expression` is a projection, each element is written as soon as it is projected,
+/// rather than collecting the whole array first.
+pub fn search_to_writer<W: io::Write>(
+    expression: &Expression,
+    data: Value,
+    mut writer: W,
+) -> Result<(), SearchError> {
+    let write_err = |error: io::Error| SearchError::Write(error.to_string());
+    let vars = Map::new();
+    let context = Context::new(&vars, SearchOptions::default()).memoizing(expression);
+    let lhs = match expression {
+        Expression::FilterProjection { lhs, .. }
+        | Expression::SliceProjection { lhs, .. }
+        | Expression::ObjectProjection { lhs, .. }
+        | Expression::FlattenProjection { lhs, .. }
+            if context.shared.is_empty() =>
+        {
+            lhs
+        }
+        _ => {
+            let result = expression.search(data)?;
+            return serde_json::to_writer(writer, &result).map_err(|error| write_err(error.into()));
+        }
+    };
+    let _guard = context.enter()?;
+    let lhs = lhs.evaluate(data, &context)?;
+    let mut first = true;
+    let projected = expression.project_each(lhs, &context, |result| {
+        let separator: &[u8] = if first { b"[" } else { b"," };
+        first = false;
+        writer.write_all(separator).map_err(write_err)?;
+        serde_json::to_writer(&mut writer, &result).map_err(|error| write_err(error.into()))
+    })?;
+    let end: &[u8] = match (projected, first) {
+        (false, _) => b"null",
+        (true, true) => b"[]",
+        (true, false) => b"]",
+    };
+    writer.write_all(end).map_err(write_err)
+}
+
+/// Everything an expression can refer to besides the current node, and how far we've gone.
+struct Context<'a, 'data> {
+    vars: &'a Map<String, Value>,
+    options: SearchOptions,
+    depth: Cell<usize>,
+    steps: Cell<usize>,
+    /// See [`Expression::shared_subexpressions`]
+    shared: HashMap<*const Expression, usize>,
+    /// Results of `shared` subexpressions, by their group and the node in the borrowed document they were evaluated against
+    memo: RefCell<HashMap<(usize, *const Value), Cow<'data, Value>>>,
+}
+
+impl<'a> Context<'a, '_> {
+    fn new(vars: &'a Map<String, Value>, options: SearchOptions) -> Self {
+        Self {
+            vars,
+            options,
+            depth: Cell::new(0),
+            steps: Cell::new(0),
+            shared: HashMap::new(),
+            memo: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Share the results of `expression`'s repeated subexpressions.
+    fn memoizing(mut self, expression: &Expression) -> Self {
+        self.shared = expression.shared_subexpressions();
+        self
+    }
+
+    /// Account for evaluating a subexpression, which is finished when the returned guard is dropped.
+    fn enter(&self) -> Result<DepthGuard<'_>, SearchError> {
+        let depth = self.depth.get() + 1;
+        let steps = self.steps.get() + 1;
+        if self.options.max_depth.is_some_and(|max| depth > max)
+            || self.options.max_steps.is_some_and(|max| steps > max)
+        {
+            return Err(SearchError::LimitExceeded);
+        }
+        self.depth.set(depth);
+        self.steps.set(steps);
+        Ok(DepthGuard(&self.depth))
+    }
+}
+
+struct DepthGuard<'a>(&'a Cell<usize>);
+
+impl Drop for DepthGuard<'_> {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() - 1)
+    }
+}
+
+impl Expression {
+    pub fn search(&self, data: Value) -> Result<Value, SearchError> {
+        self.search_with(data, &Map::new())
+    }
+
+    pub fn search_with(
+        &self,
+        data: Value,
+        vars: &Map<String, Value>,
+    ) -> Result<Value, SearchError> {
+        self.search_with_options(data, vars, SearchOptions::default())
+    }
+
+    pub fn search_with_options(
+        &self,
+        data: Value,
+        vars: &Map<String, Value>,
+        options: SearchOptions,
+    ) -> Result<Value, SearchError> {
+        let context = Context::new(vars, options).memoizing(self);
+        match context.shared.is_empty() {
+            true => self.evaluate(data, &context),
+            // Memoizing needs stable addresses for nodes
+            false => Ok(self.evaluate_ref(&data, &context)?.into_owned()),
+        }
+    }
+
+    /// Like [`Expression::search`], but borrowing `data`.
+    /// Identifiers and indices are followed by reference, so only the parts of `data` they lead to are cloned,
+    /// rather than the whole document.
+    pub fn search_ref(&self, data: &Value) -> Result<Value, SearchError> {
+        let vars = Map::new();
+        let context = Context::new(&vars, SearchOptions::default()).memoizing(self);
+        Ok(self.evaluate_ref(data, &context)?.into_owned())
+    }
+
+    /// Subexpressions which appear more than once, and are worth evaluating only once per node.
+    /// Each is assigned a group, shared by the subexpressions which are equal.
+    ///
+    /// Only operators and multiselects evaluate more than one operand against the same node, so without them this is empty.
+    fn shared_subexpressions(&self) -> HashMap<*const Expression, usize> {
+        // Each node comes before its descendants
+        let mut nodes = Vec::new();
+        let mut stack = vec![self];
+        while let Some(expression) = stack.pop() {
+            nodes.push(expression);
+            stack.extend(expression.children());
+        }
+        let mut shared = HashMap::new();
+        if !nodes.iter().any(|node| {
+            matches!(
+                node,
+                Expression::Comparison(..)
+                    | Expression::Or(..)
+                    | Expression::And(..)
+                    | Expression::MultiSelectHash(_)
+            )
+        }) {
+            return shared;
+        }
+        // Number equal nodes the same, from the leaves up, so each node is keyed by its own contents and its
+        // children's numbers, rather than by its whole, arbitrarily deep, subtree
+        let mut ids = HashMap::<*const Expression, usize>::new();
+        let mut interned = HashMap::new();
+        let mut groups = HashMap::<usize, Vec<*const Expression>>::new();
+        for node in nodes.into_iter().rev() {
+            let children = node
+                .children()
+                .into_iter()
+                .map(|child| ids[&(child as *const Expression)])
+                .collect::<Vec<_>>();
+            let key = (mem::discriminant(node), node.contents(), children);
+            let next = interned.len();
+            let id = *interned.entry(key).or_insert(next);
+            ids.insert(node, id);
+            match node.children().is_empty() {
+                // Cheaper to evaluate than to look up
+                true => (),
+                false => groups.entry(id).or_default().push(node),
+            }
+        }
+        for (group, nodes) in groups {
+            if nodes.len() > 1 {
+                shared.extend(nodes.into_iter().map(|node| (node, group)))
+            }
+        }
+        shared
+    }
+
+    /// The operands of this expression, in a fixed order.
+    fn children(&self) -> Vec<&Expression> {
+        match self {
+            Expression::Current
+            | Expression::Identifier(_)
+            | Expression::Index(_)
+            | Expression::Variable(_)
+            | Expression::Literal(_) => vec![],
+            Expression::Not(expression) | Expression::ExprRef(expression) => vec![expression],
+            Expression::Subexpression(lhs, rhs)
+            | Expression::Comparison(_, lhs, rhs)
+            | Expression::Or(lhs, rhs)
+            | Expression::And(lhs, rhs)
+            | Expression::Pipe(lhs, rhs)
+            | Expression::SliceProjection { lhs, rhs, .. }
+            | Expression::ObjectProjection { lhs, rhs }
+            | Expression::FlattenProjection { lhs, rhs } => vec![lhs, rhs],
+            Expression::FilterProjection {
+                lhs,
+                condition,
+                rhs,
+            } => vec![lhs, condition, rhs],
+            Expression::FunctionCall { args, .. } => args.iter().collect(),
+            Expression::MultiSelectHash(entries) => {
+                entries.iter().map(|(_, value)| value).collect()
+            }
+        }
+    }
+
+    /// Everything which distinguishes this expression from another of the same kind, besides its [`children`](Self::children).
+    fn contents(&self) -> Vec<String> {
+        match self {
+            // These have no children, so print without recursing
+            Expression::Current
+            | Expression::Identifier(_)
+            | Expression::Index(_)
+            | Expression::Variable(_)
+            | Expression::Literal(_) => vec![self.to_string()],
+            Expression::Comparison(comparator, ..) => vec![comparator.to_string()],
+            Expression::SliceProjection { slice, .. } => vec![slice.to_string()],
+            Expression::FunctionCall { name, .. } => vec![name.clone()],
+            Expression::MultiSelectHash(entries) => {
+                entries.iter().map(|(key, _)| key.clone()).collect()
+            }
+            Expression::Not(_)
+            | Expression::ExprRef(_)
+            | Expression::Subexpression(..)
+            | Expression::Or(..)
+            | Expression::And(..)
+            | Expression::Pipe(..)
+            | Expression::FilterProjection { .. }
+            | Expression::ObjectProjection { .. }
+            | Expression::FlattenProjection { .. } => vec![],
+        }
+    }
+
+    /// See [`trace`]. The last is always `self`.
+    fn prefixes(&self) -> Vec<Expression> {
+        // Everything projected is part of the projection, so look inside for steps
+        let projections =
+            |lhs: &Expression, rhs: &Expression, with_rhs: &dyn Fn(Expression) -> Expression| {
+                let mut prefixes = lhs.prefixes();
+                prefixes.push(with_rhs(Expression::Current));
+                prefixes.extend(
+                    rhs.prefixes()
+                        .into_iter()
+                        .filter(|prefix| *prefix != Expression::Current)
+                        .map(with_rhs),
+                );
+                prefixes
+            };
+        match self {
+            Expression::Subexpression(lhs, _) | Expression::Pipe(lhs, _) => {
+                let mut prefixes = lhs.prefixes();
+                prefixes.push(self.clone());
+                prefixes
+            }
+            Expression::FilterProjection {
+                lhs,
+                condition,
+                rhs,
+            } => projections(lhs, rhs, &|rhs| Expression::FilterProjection {
+                lhs: lhs.clone(),
+                condition: condition.clone(),
+                rhs: Box::new(rhs),
+            }),
+            Expression::SliceProjection {
+                lhs, slice, rhs, ..
+            } => projections(lhs, rhs, &|rhs| Expression::SliceProjection {
+                lhs: lhs.clone(),
+                slice: *slice,
+                rhs: Box::new(rhs),
+                cache: SliceCache::default(),
+            }),
+            Expression::ObjectProjection { lhs, rhs } => {
+                projections(lhs, rhs, &|rhs| Expression::ObjectProjection {
+                    lhs: lhs.clone(),
+                    rhs: Box::new(rhs),
+                })
+            }
+            Expression::FlattenProjection { lhs, rhs } => {
+                projections(lhs, rhs, &|rhs| Expression::FlattenProjection {
+                    lhs: lhs.clone(),
+                    rhs: Box::new(rhs),
+                })
+            }
+            _ => vec![self.clone()],
+        }
+    }
+
+    fn evaluate_ref<'a>(
+        &self,
+        value: &'a Value,
+        context: &Context<'_, 'a>,
+    ) -> Result<Cow<'a, Value>, SearchError> {
+        let _guard = context.enter()?;
+        let Some(&group) = context.shared.get(&(self as *const Expression)) else {
+            return self.evaluate_ref_unshared(value, context);
+        };
+        let key = (group, value as *const Value);
+        if let Some(result) = context.memo.borrow().get(&key) {
+            return Ok(result.clone());
+        }
+        let result = self.evaluate_ref_unshared(value, context)?;
+        context.memo.borrow_mut().insert(key, result.clone());
+        Ok(result)
+    }
+
+    fn evaluate_ref_unshared<'a>(
+        &self,
+        value: &'a Value,
+        context: &Context<'_, 'a>,
+    ) -> Result<Cow<'a, Value>, SearchError> {
+        static NULL: Value = Null;
+        match self {
+            Expression::Current => Ok(Cow::Borrowed(value)),
+            Expression::Identifier(key) => Ok(Cow::Borrowed(value.get(key).unwrap_or(&NULL))),
+            Expression::Index(index) => Ok(Cow::Borrowed(match value {
+                Array(vec) => normalize_index(*index, vec.len()).map_or(&NULL, |i| &vec[i]),
+                _ => &NULL,
+            })),
+            Expression::Subexpression(lhs, rhs) | Expression::Pipe(lhs, rhs) => {
+                match lhs.evaluate_ref(value, context)? {
+                    Cow::Borrowed(value) => rhs.evaluate_ref(value, context),
+                    Cow::Owned(value) => rhs.evaluate(value, context).map(Cow::Owned),
+                }
+            }
+            Expression::Not(expression) => Ok(Cow::Owned(Bool(!is_truthy(
+                &*expression.evaluate_ref(value, context)?,
+            )))),
+            Expression::Or(lhs, rhs) => match lhs.evaluate_ref(value, context)? {
+                lhs if is_truthy(&lhs) => Ok(lhs),
+                _ => rhs.evaluate_ref(value, context),
+            },
+            Expression::And(lhs, rhs) => match lhs.evaluate_ref(value, context)? {
+                lhs if !is_truthy(&lhs) => Ok(lhs),
+                _ => rhs.evaluate_ref(value, context),
+            },
+            Expression::Comparison(comparator, lhs, rhs) => {
+                let lhs = lhs.evaluate_ref(value, context)?;
+                let rhs = rhs.evaluate_ref(value, context)?;
+                Ok(Cow::Owned(compare(*comparator, &lhs, &rhs)))
+            }
+            Expression::FilterProjection { lhs, .. }
+            | Expression::SliceProjection { lhs, .. }
+            | Expression::ObjectProjection { lhs, .. }
+            | Expression::FlattenProjection { lhs, .. } => {
+                let lhs = lhs.evaluate_ref(value, context)?.into_owned();
+                self.project_lhs(lhs, context).map(Cow::Owned)
+            }
+            Expression::FunctionCall { name, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| match arg {
+                        Expression::ExprRef(expression) => Ok(Argument::Expression(expression)),
+                        arg => Ok(Argument::Value(
+                            arg.evaluate_ref(value, context)?.into_owned(),
+                        )),
+                    })
+                    .collect::<Result<_, SearchError>>()?;
+                functions::call(name, args, |expression, value| {
+                    expression.evaluate(value, context)
+                })
+                .map(Cow::Owned)
+            }
+            Expression::MultiSelectHash(_) if value.is_null() => Ok(Cow::Borrowed(&NULL)),
+            Expression::MultiSelectHash(entries) => entries
+                .iter()
+                .map(|(key, expression)| {
+                    Ok((
+                        key.clone(),
+                        expression.evaluate_ref(value, context)?.into_owned(),
+                    ))
+                })
+                .collect::<Result<_, SearchError>>()
+                .map(|map| Cow::Owned(Object(map))),
+            _ => self.evaluate(value.clone(), context).map(Cow::Owned),
+        }
+    }
+
+    fn evaluate(&self, value: Value, context: &Context) -> Result<Value, SearchError> {
+        let _guard = context.enter()?;
+        match self {
+            Expression::Current => Ok(value),
+            // These are always lenient, even with `strict-debug`, as the spec says they're `null`
+            Expression::Identifier(key) => Ok(lenient_identify(value, key)),
+            Expression::Index(index) => Ok(lenient_index(value, *index)),
+            Expression::Subexpression(lhs, rhs) => {
+                let value = lhs.evaluate(value, context)?;
+                rhs.evaluate(value, context)
+            }
+            Expression::Variable(name) => context
+                .vars
+                .get(name)
+                .cloned()
+                .ok_or_else(|| SearchError::UnboundVariable(name.clone())),
+            Expression::Literal(value) => Ok(value.clone()),
+            Expression::Comparison(comparator, lhs, rhs) => {
+                let lhs = lhs.evaluate(value.clone(), context)?;
+                let rhs = rhs.evaluate(value, context)?;
+                Ok(compare(*comparator, &lhs, &rhs))
+            }
+            Expression::Not(expression) => {
+                Ok(Bool(!is_truthy(&expression.evaluate(value, context)?)))
+            }
+            Expression::Or(lhs, rhs) => match lhs.evaluate(value.clone(), context)? {
+                lhs if is_truthy(&lhs) => Ok(lhs),
+                _ => rhs.evaluate(value, context),
+            },
+            Expression::And(lhs, rhs) => match lhs.evaluate(value.clone(), context)? {
+                lhs if !is_truthy(&lhs) => Ok(lhs),
+                _ => rhs.evaluate(value, context),
+            },
+            Expression::Pipe(lhs, rhs) => {
+                let value = lhs.evaluate(value, context)?;
+                rhs.evaluate(value, context)
+            }
+            Expression::FilterProjection { lhs, .. }
+            | Expression::SliceProjection { lhs, .. }
+            | Expression::ObjectProjection { lhs, .. }
+            | Expression::FlattenProjection { lhs, .. } => {
+                let lhs = lhs.evaluate(value, context)?;
+                self.project_lhs(lhs, context)
+            }
+            Expression::FunctionCall { name, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| match arg {
+                        Expression::ExprRef(expression) => Ok(Argument::Expression(expression)),
+                        arg => Ok(Argument::Value(arg.evaluate(value.clone(), context)?)),
+                    })
+                    .collect::<Result<_, SearchError>>()?;
+                functions::call(name, args, |expression, value| {
+                    expression.evaluate(value, context)
+                })
+            }
+            Expression::ExprRef(_) => Ok(Null),
+            Expression::MultiSelectHash(_) if value.is_null() => Ok(Null),
+            Expression::MultiSelectHash(entries) => entries
+                .iter()
+                .map(|(key, expression)| {
+                    Ok((key.clone(), expression.evaluate(value.clone(), context)?))
+                })
+                .collect::<Result<_, SearchError>>()
+                .map(Object),
+        }
+    }
+
+    /// Finish evaluating a projection, given the result of its `lhs`.
+    fn project_lhs(&self, lhs: Value, context: &Context) -> Result<Value, SearchError> {
+        let mut results = Vec::new();
+        let projected = self.project_each(lhs, context, |result| {
+            results.push(result);
+            Ok(())
+        })?;
+        Ok(match projected {
+            true => Array(results),
+            false => Null,
+        })
+    }
+
+    /// Finish evaluating a projection, given the result of its `lhs`, passing each non-`null` result to `f` in turn.
+    /// Returns `false` without calling `f` if `lhs` isn't the type the projection is over, so the result is `null`.
+    fn project_each(
+        &self,
+        lhs: Value,
+        context: &Context,
+        mut f: impl FnMut(Value) -> Result<(), SearchError>,
+    ) -> Result<bool, SearchError> {
+        let (elements, condition, rhs) = match (self, lhs) {
+            (Expression::FilterProjection { condition, rhs, .. }, Array(vec)) => {
+                (vec, Some(condition), rhs)
+            }
+            (
+                Expression::SliceProjection {
+                    slice, rhs, cache, ..
+                },
+                Array(vec),
+            ) => (cache.slice(*slice, vec), None, rhs),
+            (Expression::ObjectProjection { rhs, .. }, Object(map)) => (
+                map.into_iter().map(|(_key, value)| value).collect(),
+                None,
+                rhs,
+            ),
+            (Expression::FlattenProjection { rhs, .. }, Array(vec)) => {
+                let mut flattened = Vec::with_capacity(vec.len());
+                for element in vec {
+                    match element {
+                        Array(mut inner) => flattened.append(&mut inner),
+                        other => flattened.push(other),
+                    }
+                }
+                (flattened, None, rhs)
+            }
+            (
+                Expression::FilterProjection { .. }
+                | Expression::SliceProjection { .. }
+                | Expression::ObjectProjection { .. }
+                | Expression::FlattenProjection { .. },
+                _,
+            ) => return Ok(false),
+            _ => unreachable!("Only called for projections"),
+        };
+        for element in elements {
+            if let Some(condition) = condition {
+                if !is_truthy(&condition.evaluate(element.clone(), context)?) {
+                    continue;
+                }
+            }
+            let result = rhs.evaluate(element, context)?;
+            if !result.is_null() {
+                f(result)?
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl SliceCache {
+    fn slice(&self, slice: JMESSlice, mut vec: Vec<Value>) -> Vec<Value> {
+        let mut cache = self.0.lock().expect("Never poisoned");
+        match &*cache {
+            Some((len, _)) if *len == vec.len() => (),
+            _ => *cache = Some((vec.len(), slice.indices(vec.len()))),
+        }
+        let (_, indices) = cache.as_ref().expect("Just populated");
+        // Slices never repeat an index, so we can move rather than clone
+        indices.iter().map(|&i| mem::take(&mut vec[i])).collect()
+    }
+}
+
+/// Equality is [`semantically_eq`], and ordering is only defined for numbers, else `null`.
+fn compare(comparator: Comparator, lhs: &Value, rhs: &Value) -> Value {
+    let ordering = match (comparator, lhs, rhs) {
+        (Comparator::Equal, ..) => return Bool(semantically_eq(lhs, rhs)),
+        (Comparator::NotEqual, ..) => return Bool(!semantically_eq(lhs, rhs)),
+        (_, Number(lhs), Number(rhs)) => compare_numbers(lhs, rhs),
+        _ => return Null,
+    };
+    Bool(match comparator {
+        Comparator::LessThan => ordering.is_lt(),
+        Comparator::LessThanOrEqual => ordering.is_le(),
+        Comparator::GreaterThan => ordering.is_gt(),
+        Comparator::GreaterThanOrEqual => ordering.is_ge(),
+        Comparator::Equal | Comparator::NotEqual => unreachable!("Handled above"),
+    })
+}
+
+/// Empty arrays, objects and strings, `false` and `null` are falsey.
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Null => false,
+        Bool(b) => *b,
+        Value::String(s) => !s.is_empty(),
+        Array(vec) => !vec.is_empty(),
+        Object(map) => !map.is_empty(),
+        Number(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn items() -> Value {
+        json!({"items": [
+            {"name": "cheap", "price": 5},
+            {"name": "pricey", "price": 15},
+            {"name": "free", "price": 0},
+            {"name": "unknown"}
+        ]})
+    }
+
+    #[test]
+    fn search_with_variables() {
+        let vars = json!({"max": 10}).as_object().unwrap().clone();
+        assert_eq!(
+            search_with("items[?price < $max].name", items(), &vars),
+            Ok(json!(["cheap", "free"]))
+        );
+        assert_eq!(
+            search_with("items[?price < $max]", items(), &vars),
+            Ok(json!([
+                {"name": "cheap", "price": 5},
+                {"name": "free", "price": 0}
+            ]))
+        );
+        assert_eq!(
+            search("items[?price < $max]", items()),
+            Err(SearchError::UnboundVariable("max".into()))
+        );
+    }
+
+    fn complex() -> Value {
+        json!({"a": {
+          "b": {
+            "c": [
+              {"d": [0, [1, 2]]},
+              {"d": [3, 4]}
+            ]
+          }
+        }})
+    }
+
+    #[test]
+    fn search_many() {
+        assert_eq!(
+            super::search_many(
+                &["a.b.c[0].d[0]", "a.b.c[1].d", "a.b.c[-1].missing", "a["],
+                &complex()
+            ),
+            vec![
+                Ok(json!(0)),
+                Ok(json!([3, 4])),
+                Ok(json!(null)),
+                Err(SearchError::Parse(ParseExpressionError::UnexpectedEnd))
+            ]
+        );
+    }
+
+    #[test]
+    fn search_ref() {
+        for expression in [
+            "a",
+            "a.b.c[0]",
+            "a.b.c[5].d",
+            "x.y",
+            "a.b.c[1:].d",
+            "a.b.c[?d].d[0]",
+            "x || a.b",
+            "a && x",
+            "a.b.c[:].d | [1]",
+        ] {
+            let expression = expression.parse::<Expression>().unwrap();
+            assert_eq!(
+                expression.search_ref(&complex()),
+                expression.search(complex())
+            );
+        }
+    }
+
+    #[test]
+    fn limits() {
+        let deep = format!("a{}", ".a".repeat(10_000))
+            .parse::<Expression>()
+            .unwrap();
+        let options = SearchOptions {
+            max_depth: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(
+            deep.search_with_options(json!({}), &Map::new(), options),
+            Err(SearchError::LimitExceeded)
+        );
+        // Finding shared subexpressions happens before any limit is checked, so mustn't recurse either
+        let deep_or = format!("a{} || b", ".a".repeat(200_000));
+        assert_eq!(
+            deep_or.parse::<Expression>().unwrap().search_with_options(
+                json!({}),
+                &Map::new(),
+                options
+            ),
+            Err(SearchError::LimitExceeded)
+        );
+        // Nor must dropping the expression, which `search_with_options` does itself
+        assert_eq!(
+            search_with_options(&deep_or, json!({}), &Map::new(), options),
+            Err(SearchError::LimitExceeded)
+        );
+        let shallow = "a.a.a".parse::<Expression>().unwrap();
+        assert_eq!(
+            shallow.search_with_options(json!({"a": {"a": {"a": 1}}}), &Map::new(), options),
+            Ok(json!(1))
+        );
+
+        let options = SearchOptions {
+            max_steps: Some(100),
+            ..Default::default()
+        };
+        let many = Value::Array(vec![json!(1); 100]);
+        assert_eq!(
+            search_with_options("[?@]", many.clone(), &Map::new(), options),
+            Err(SearchError::LimitExceeded)
+        );
+        assert_eq!(
+            search_with_options("[:10]", many, &Map::new(), options),
+            Ok(Value::Array(vec![json!(1); 10]))
+        );
+    }
+
+    #[test]
+    fn grouping() {
+        let data = json!({"a": {"c": 1}, "b": {"c": 2}});
+        assert_eq!(search("(a || b).c", data.clone()), Ok(json!(1)));
+        assert_eq!(search("a || b.c", data.clone()), Ok(json!({"c": 1})));
+        assert_eq!(search("(x || b).c", data.clone()), Ok(json!(2)));
+        assert_eq!(search("a && b.c", data.clone()), Ok(json!(2)));
+        assert_eq!(search("x && b", data), Ok(json!(null)));
+        // Pipes stop projections
+        assert_eq!(search("items[?price].name[0]", items()), Ok(json!([])));
+        assert_eq!(
+            search("items[?price].name | [0]", items()),
+            Ok(json!("cheap"))
+        );
+    }
+
+    #[test]
+    fn shared_subexpressions_are_evaluated_once() {
+        let steps = |expression: &str, memoize: bool| {
+            let expression = expression.parse::<Expression>().unwrap();
+            let vars = Map::new();
+            let mut context = Context::new(&vars, SearchOptions::default());
+            if memoize {
+                context = context.memoizing(&expression);
+            }
+            let data = complex();
+            let result = expression.evaluate_ref(&data, &context).unwrap();
+            (result.into_owned(), context.steps.get())
+        };
+        let (_, once) = steps("a.b.c[:].d", false);
+        let (naive, naive_steps) = steps("a.b.c[:].d == a.b.c[:].d", false);
+        let (memoized, memoized_steps) = steps("a.b.c[:].d == a.b.c[:].d", true);
+        assert_eq!(naive, memoized);
+        assert_eq!(naive_steps, 1 + 2 * once);
+        // Just the lookup for the second
+        assert_eq!(memoized_steps, 1 + once + 1);
+        assert_eq!(
+            search("a.b.c[0].x || a.b.c[0]", complex()),
+            Ok(json!({"d": [0, [1, 2]]}))
+        );
+        // Only equal subexpressions are shared, and `a` and `x` make every ancestor differ
+        let (_, distinct_steps) = steps("a.b.c[:].d == x.b.c[:].d", true);
+        let (_, naive_steps) = steps("a.b.c[:].d == x.b.c[:].d", false);
+        assert_eq!(distinct_steps, naive_steps);
+    }
+
+    #[test]
+    fn chained_indices() {
+        assert_eq!(search("[1][0]", json!([[0, 1], [2, 3]])), Ok(json!(2)));
+        assert_eq!(search("[-1][-1]", json!([[0, 1], [2, 3]])), Ok(json!(3)));
+        assert_eq!(
+            search("[0][0][0]", json!([[0, 1], [2, 3]])),
+            Ok(json!(null))
+        );
+        assert_eq!(
+            search("a[0].b[1]", json!({"a": [{"b": [4, 5]}]})),
+            Ok(json!(5))
+        );
+        assert_eq!(search("a.b.c[0].d[1][0]", complex()), Ok(json!(1)));
+    }
+
+    #[test]
+    fn quoted_identifiers() {
+        let data = json!({"a\tb": {"c\"d": 1}});
+        assert_eq!(search(r#""a\tb"."c\"d""#, data), Ok(json!(1)));
+    }
+
+    #[test]
+    fn filter() {
+        assert_eq!(
+            search("items[?name == name].name", items()),
+            Ok(json!(["cheap", "pricey", "free", "unknown"]))
+        );
+        // Truthiness, not just booleans
+        assert_eq!(
+            search("items[?price].name", items()),
+            Ok(json!(["cheap", "pricey", "free"]))
+        );
+        assert_eq!(search("items[?@ != @]", items()), Ok(json!([])));
+        assert_eq!(search("[?price]", items()), Ok(json!(null)));
+        assert!(matches!(search("[?", items()), Err(SearchError::Parse(_))));
+    }
+
+    #[test]
+    fn filter_current() {
+        assert_eq!(search("[?@ > `5`]", json!([1, 5, 10])), Ok(json!([10])));
+        assert_eq!(
+            search("[?`5` <= @]", json!([1, 5, 10, "a"])),
+            Ok(json!([5, 10]))
+        );
+        // `@` is the element being filtered, however deeply nested
+        assert_eq!(
+            search("[?@[?@ > `1`]]", json!([[0, 1], [1, 2], [3]])),
+            Ok(json!([[1, 2], [3]]))
+        );
+        assert_eq!(
+            search(
+                "[?length(@[?@ > `1`]) == `1`] | [0]",
+                json!([[0, 1], [1, 2], [3]])
+            ),
+            Ok(json!([1, 2]))
+        );
+        assert_eq!(
+            search("items[?@.price > `5`].name", items()),
+            Ok(json!(["pricey"]))
+        );
+        // The filter's `rhs` sees the element, not the array
+        assert_eq!(
+            search("[?@ > `1`].to_string(@)", json!([1, 2, 3])),
+            Ok(json!(["2", "3"]))
+        );
+    }
+
+    #[test]
+    fn slice() {
+        assert_eq!(
+            search("items[1:3].name", items()),
+            Ok(json!(["pricey", "free"]))
+        );
+        assert_eq!(
+            search("items[::-2].name", items()),
+            Ok(json!(["unknown", "pricey"]))
+        );
+        assert_eq!(search("items[:0]", items()), Ok(json!([])));
+        assert_eq!(search("[:]", items()), Ok(json!(null)));
+    }
+
+    #[test]
+    fn not() {
+        let people = json!([
+            {"first": "James", "last": "d"},
+            {"first": "Jacob", "last": "e"},
+            {"first": "Jayden", "last": "f"},
+            {"missing": "different"}
+        ]);
+        assert_eq!(
+            search("[?!first]", people.clone()),
+            Ok(json!([{"missing": "different"}]))
+        );
+        assert_eq!(
+            search("[?!missing].first", people.clone()),
+            Ok(json!(["James", "Jacob", "Jayden"]))
+        );
+        assert_eq!(search("[?!@]", people.clone()), Ok(json!([])));
+        assert_eq!(search("[?!!@]", people.clone()), Ok(people));
+        assert_eq!(search("!items", items()), Ok(json!(false)));
+        assert_eq!(search("!missing", items()), Ok(json!(true)));
+    }
+
+    #[test]
+    fn search_to_writer() {
+        for expression in [
+            "items[?price].name",
+            "items[:]",
+            "items[5:]",
+            "items.*",
+            "items[0].*",
+            "items[0]",
+            "items[?price == price].price",
+        ] {
+            let expression = expression.parse::<Expression>().unwrap();
+            let mut written = Vec::new();
+            super::search_to_writer(&expression, items(), &mut written).unwrap();
+            assert_eq!(
+                String::from_utf8(written).unwrap(),
+                expression.search(items()).unwrap().to_string(),
+                "{}",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn function_calls() {
+        let people = json!({"people": [
+            {"first": "James", "last": "d"},
+            {"first": "Jacob", "last": "e"},
+            {"first": "Jayden", "last": "f"},
+            {"missing": "different"}
+        ]});
+        assert_eq!(search("length(people)", people.clone()), Ok(json!(4)));
+        assert_eq!(
+            search("people[?starts_with(first, 'Ja')].last", people.clone()),
+            Ok(json!(["d", "e", "f"]))
+        );
+        assert_eq!(
+            search(
+                "people[?!starts_with(first, 'James')].first",
+                people.clone()
+            ),
+            Ok(json!(["Jacob", "Jayden"]))
+        );
+        assert_eq!(
+            search("join(', ', people[:].first)", people.clone()),
+            Ok(json!("James, Jacob, Jayden"))
+        );
+        assert_eq!(
+            search("people[:].length(@)", people.clone()),
+            Ok(json!([2, 2, 2, 1]))
+        );
+        assert_eq!(
+            search("max(items[:].price) || nope()", items()),
+            Ok(json!(15))
+        );
+        assert_eq!(
+            search("nope()", items()),
+            Err(SearchError::UnknownFunction("nope".into()))
+        );
+        assert_eq!(
+            search("sum(items[:].price) == sum(items[:].price)", items()),
+            Ok(json!(true))
+        );
+    }
+
+    #[test]
+    fn pipe_into_functions() {
+        let data = json!({"ops": {
+            "functionC": {"variadic": true},
+            "functionA": {"numArgs": 2},
+            "functionB": {"numArgs": 3}
+        }});
+        let sorted = json!(["functionA", "functionB", "functionC"]);
+        assert_eq!(
+            search("keys(ops) | sort(@)", data.clone()),
+            Ok(sorted.clone())
+        );
+        assert_eq!(search("keys(@) | sort(@)", data["ops"].clone()), Ok(sorted));
+        // The pipe takes the whole projection, rather than projecting `length` over it
+        assert_eq!(
+            search("ops.*.numArgs | length(@)", data.clone()),
+            Ok(json!(2))
+        );
+        assert_eq!(
+            search("ops.*.numArgs.length(@)", data.clone()),
+            Ok(json!([]))
+        );
+        assert_eq!(
+            search("values(ops)[:].numArgs | sort(@) | [-1]", data),
+            Ok(json!(3))
+        );
+    }
+
+    #[test]
+    fn postfix_after_function_calls() {
+        let data = json!({"ops": {
+            "functionC": {"variadic": true},
+            "functionA": {"numArgs": 2},
+            "functionB": {"numArgs": 3}
+        }});
+        assert_eq!(
+            search("sort(keys(ops))[0]", data.clone()),
+            Ok(json!("functionA"))
+        );
+        assert_eq!(
+            search("sort(keys(ops))[-1]", data.clone()),
+            Ok(json!("functionC"))
+        );
+        assert_eq!(
+            search("sort(keys(ops))[1:]", data.clone()),
+            Ok(json!(["functionB", "functionC"]))
+        );
+        assert_eq!(
+            search(
+                "sort_by(values(ops)[?numArgs], &numArgs)[:].numArgs",
+                data.clone()
+            ),
+            Ok(json!([2, 3]))
+        );
+        assert_eq!(
+            search("merge(ops, `{}`).functionB.numArgs", data.clone()),
+            Ok(json!(3))
+        );
+        assert_eq!(
+            search("values(ops)[?variadic] | length(@)", data.clone()),
+            Ok(json!(1))
+        );
+        assert_eq!(search("keys(ops)[5]", data), Ok(Null));
+    }
+
+    #[test]
+    fn expression_references() {
+        let people = json!({"people": [
+            {"first": "James", "age": 30},
+            {"first": "Jacob", "age": 25},
+            {"first": "Jayden", "age": 35}
+        ]});
+        assert_eq!(
+            search("sort_by(people, &first)[:].first", people.clone()),
+            Ok(json!(["Jacob", "James", "Jayden"]))
+        );
+        assert_eq!(
+            search("sort_by(people, &first)", people.clone()),
+            Ok(json!([
+                {"first": "Jacob", "age": 25},
+                {"first": "James", "age": 30},
+                {"first": "Jayden", "age": 35}
+            ]))
+        );
+        assert_eq!(
+            search("max_by(people, &age).first", people.clone()),
+            Ok(json!("Jayden"))
+        );
+        assert_eq!(
+            search("min_by(people, &age).first", people.clone()),
+            Ok(json!("Jacob"))
+        );
+        assert_eq!(
+            search("map(&age > `28`, people)", people.clone()),
+            Ok(json!([true, false, true]))
+        );
+        // Unsortable keys, and references outside functions
+        assert_eq!(search("sort_by(people, &nope)", people.clone()), Ok(Null));
+        assert_eq!(search("&first", people.clone()), Ok(Null));
+        assert_eq!(search("length(&first)", people), Ok(Null));
+    }
+
+    #[test]
+    fn evaluate_prefix() {
+        let data = complex();
+        assert_eq!(
+            super::evaluate_prefix("a.b.", &data),
+            Ok((data["a"]["b"].clone(), vec!["c".to_string()]))
+        );
+        assert_eq!(
+            super::evaluate_prefix("a.b.c[0].", &data),
+            Ok((json!({"d": [0, [1, 2]]}), vec!["d".to_string()]))
+        );
+        assert_eq!(
+            super::evaluate_prefix("a.b.c[", &data),
+            Ok((data["a"]["b"]["c"].clone(), vec![]))
+        );
+        assert_eq!(
+            super::evaluate_prefix(" ", &data),
+            Ok((data.clone(), vec!["a".to_string()]))
+        );
+        assert_eq!(
+            super::evaluate_prefix("]", &data),
+            Err(SearchError::Parse(ParseExpressionError::UnexpectedToken(0)))
+        );
+    }
+
+    #[test]
+    fn trace() {
+        let data = json!({"reservations": [
+            {"instances": [{"state": "running"}, {"state": "stopped"}]},
+            {"instances": [{"state": "running"}]}
+        ]});
+        let expression = "reservations[:].instances[0].nope"
+            .parse::<Expression>()
+            .unwrap();
+        assert_eq!(
+            super::trace(&expression, data.clone()),
+            Ok(vec![
+                ("reservations".into(), data["reservations"].clone()),
+                ("reservations[:]".into(), data["reservations"].clone()),
+                (
+                    "reservations[:].instances".into(),
+                    json!([
+                        [{"state": "running"}, {"state": "stopped"}],
+                        [{"state": "running"}]
+                    ])
+                ),
+                (
+                    "reservations[:].instances[0]".into(),
+                    json!([{"state": "running"}, {"state": "running"}])
+                ),
+                ("reservations[:].instances[0].nope".into(), json!([])),
+            ])
+        );
+        let expression = "reservations[].instances".parse::<Expression>().unwrap();
+        assert_eq!(
+            super::trace(&expression, data.clone()),
+            Ok(vec![
+                ("reservations".into(), data["reservations"].clone()),
+                ("reservations[]".into(), data["reservations"].clone()),
+                (
+                    "reservations[].instances".into(),
+                    json!([
+                        [{"state": "running"}, {"state": "stopped"}],
+                        [{"state": "running"}]
+                    ])
+                ),
+            ])
+        );
+        let expression = "reservations[].instances[].state"
+            .parse::<Expression>()
+            .unwrap();
+        let traced = super::trace(&expression, data.clone()).unwrap();
+        assert_eq!(
+            traced[3],
+            (
+                "reservations[].instances[]".into(),
+                json!([{"state": "running"}, {"state": "stopped"}, {"state": "running"}])
+            )
+        );
+        assert_eq!(
+            traced[4],
+            (
+                "reservations[].instances[].state".into(),
+                json!(["running", "stopped", "running"])
+            )
+        );
+        let expression = "[?a] | length(@)".parse::<Expression>().unwrap();
+        assert_eq!(
+            super::trace(&expression, json!([{"a": 1}, {}])),
+            Ok(vec![
+                ("[?a]".into(), json!([{"a": 1}])),
+                ("[?a] | length(@)".into(), json!(1)),
+            ])
+        );
+        assert_eq!(
+            super::trace(&Expression::Current, json!(1)),
+            Ok(vec![("@".into(), json!(1))])
+        );
+    }
+
+    #[test]
+    fn raw_strings() {
+        assert_eq!(search(r"'it\'s raw'", Null), Ok(json!("it's raw")));
+        assert_eq!(search(r"'\t'", Null), Ok(json!("\\t")));
+        assert_eq!(
+            search("items[?name == 'free'].price", items()),
+            Ok(json!([0]))
+        );
+    }
+
+    #[test]
+    fn pipe_then_index() {
+        let people = json!({"people": [
+            {"first": "James", "last": "d", "tags": ["x", "y"]},
+            {"first": "Jacob", "last": "e", "tags": ["z"]},
+            {"missing": "different"}
+        ]});
+        assert_eq!(
+            search("people[*].first | [0]", people.clone()),
+            Ok(json!("James"))
+        );
+        // Without the pipe, `[0]` is projected over each first name, which aren't arrays
+        assert_eq!(search("people[*].first[0]", people.clone()), Ok(json!([])));
+        assert_eq!(
+            search("people[*].tags[0]", people.clone()),
+            Ok(json!(["x", "z"]))
+        );
+        assert_eq!(
+            search("people[*].tags | [0]", people.clone()),
+            Ok(json!(["x", "y"]))
+        );
+        assert_eq!(
+            search("people[*] | [-1].missing", people.clone()),
+            Ok(json!("different"))
+        );
+        assert_eq!(search("people[*].first | [5]", people), Ok(Null));
+    }
+
+    #[test]
+    fn multiselect_hash_in_projection() {
+        let people = json!({"people": [
+            {"first": "James", "last": "d", "age": 30},
+            {"first": "Jacob", "last": "e"},
+            {"first": "Jayden", "last": "f"},
+            {"missing": "different"}
+        ]});
+        assert_eq!(
+            search("people[*].{name: first, surname: last}", people.clone()),
+            Ok(json!([
+                {"name": "James", "surname": "d"},
+                {"name": "Jacob", "surname": "e"},
+                {"name": "Jayden", "surname": "f"},
+                {"name": null, "surname": null}
+            ]))
+        );
+        // Each entry is evaluated against the element, not the whole document
+        assert_eq!(
+            search(
+                "people[?age].{who: {first: first}, old: age > `20`, all: length(@)}",
+                people.clone()
+            ),
+            Ok(json!([{"who": {"first": "James"}, "old": true, "all": 3}]))
+        );
+        // A projection inside an entry ends there, rather than continuing after the hash
+        assert_eq!(
+            search("{names: people[*].first}.names[-1]", people.clone()),
+            Ok(json!("Jayden"))
+        );
+        assert_eq!(
+            search("{a: people}.a[0].first", people.clone()),
+            Ok(json!("James"))
+        );
+        assert_eq!(search("missing.{a: b}", people.clone()), Ok(Null));
+        assert_eq!(
+            search("people[0].last | {last: @, both: @ == 'd'}", people),
+            Ok(json!({"last": "d", "both": true}))
+        );
+    }
+
+    #[test]
+    fn compare_mismatched_types() {
+        let people = json!([
+            {"name": "a", "age": 30},
+            {"name": "b", "age": "twenty"},
+            {"name": "c", "age": 5},
+            {"name": "d"}
+        ]);
+        assert_eq!(
+            search("[?age > `10`].name", people.clone()),
+            Ok(json!(["a"]))
+        );
+        // The comparison is `null`, which `!` negates to `true`, unlike `<=`
+        assert_eq!(
+            search("[?!(age > `10`)].name", people.clone()),
+            Ok(json!(["b", "c", "d"]))
+        );
+        assert_eq!(
+            search("[?age <= `10`].name", people.clone()),
+            Ok(json!(["c"]))
+        );
+        assert_eq!(search("[1].age < `10`", people.clone()), Ok(Null));
+        assert_eq!(search("[1].age == `20`", people), Ok(json!(false)));
+        assert_eq!(search("'b' > 'a'", Null), Ok(Null));
+    }
+
+    #[test]
+    fn compare_with_null() {
+        let people = json!({"people": [
+            {"first": "James", "last": "d"},
+            {"first": "Jacob", "last": "e"},
+            {"first": "Jayden", "last": "f"},
+            {"missing": "different"}
+        ]});
+        assert_eq!(
+            search("people[?first == `null`]", people.clone()),
+            Ok(json!([{"missing": "different"}]))
+        );
+        assert_eq!(
+            search("people[?first != `null`].first", people.clone()),
+            Ok(json!(["James", "Jacob", "Jayden"]))
+        );
+        // Ordering isn't defined for null, so nothing passes
+        assert_eq!(search("people[?first < `null`]", people), Ok(json!([])));
+        assert_eq!(
+            search("items[?price == `5`].name", items()),
+            Ok(json!(["cheap"]))
+        );
+        assert_eq!(
+            search("items[?price == `5.0`].name", items()),
+            Ok(json!(["cheap"]))
+        );
+    }
+
+    #[test]
+    fn object_projection() {
+        let data = json!({"a": {"x": {"b": 1}, "y": {"b": 2}, "z": {}}});
+        assert_eq!(search("a.*.b", data.clone()), Ok(json!([1, 2])));
+        assert_eq!(
+            search("a.*", data.clone()),
+            Ok(json!([{"b": 1}, {"b": 2}, {}]))
+        );
+        assert_eq!(search("*.x.b", data.clone()), Ok(json!([1])));
+        assert_eq!(search("a.x.b.*", data), Ok(json!(null)));
+        assert_eq!(
+            search("items[?price].*", items()),
+            Ok(json!([["cheap", 5], ["pricey", 15], ["free", 0]]))
+        );
+    }
+
+    #[test]
+    fn flatten_projection() {
+        let data = json!({"reservations": [
+            {"instances": [{"state": "running"}, {"state": "stopped"}]},
+            {"instances": [{"state": "terminated"}, {"state": "running"}]}
+        ]});
+        assert_eq!(
+            search("reservations[].instances[].state", data.clone()),
+            Ok(json!(["running", "stopped", "terminated", "running"]))
+        );
+        // Only one level is flattened, and elements which aren't arrays are kept
+        assert_eq!(
+            search("[]", json!([[1, [2]], 3, [], [null]])),
+            Ok(json!([1, [2], 3]))
+        );
+        assert_eq!(search("[][]", json!([[1, [2]], 3])), Ok(json!([1, 2, 3])));
+        assert_eq!(search("a[]", json!({"a": {"b": 1}})), Ok(json!(null)));
+        // Flattens the whole projection on its left, rather than each of its results
+        assert_eq!(
+            search("reservations[*].instances[] | [0]", data.clone()),
+            Ok(json!({"state": "running"}))
+        );
+        assert_eq!(
+            search("reservations[*].instances[0]", data.clone()),
+            Ok(json!([{"state": "running"}, {"state": "terminated"}]))
+        );
+        let expression = "reservations[].instances[].state"
+            .parse::<Expression>()
+            .unwrap();
+        assert_eq!(expression.search_ref(&data), expression.search(data));
+    }
+
+    #[test]
+    fn slice_cache_is_keyed_on_length() {
+        let expression = "[?@][::-1]".parse::<Expression>().unwrap();
+        let batch = json!([[0, 1, 2], [3, 4, 5], [6, 7], [8, 9, 10]]);
+        assert_eq!(
+            expression.search(batch),
+            Ok(json!([[2, 1, 0], [5, 4, 3], [7, 6], [10, 9, 8]]))
+        );
+    }
+}
@@ -0,0 +1,70 @@
+#![cfg(feature = "derive")]
+use jmespath_native::JMESPath;
+use serde_json::json;
+
+#[derive(Debug, PartialEq, JMESPath)]
+struct Person {
+    first: String,
+    last: String,
+}
+
+#[derive(Debug, PartialEq, JMESPath)]
+struct MaybeNamed {
+    r#first: Option<String>,
+}
+
+#[test]
+fn from_value() {
+    let people = json!([
+        {"first": "James", "last": "d"},
+        {"first": "Jacob", "last": "e"},
+        {"first": "Jayden", "last": "f"},
+        {"missing": "different"}
+    ]);
+    let people = people
+        .as_array()
+        .unwrap()
+        .iter()
+        .cloned()
+        .map(Person::from_value)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        people,
+        vec![
+            Some(Person {
+                first: "James".into(),
+                last: "d".into()
+            }),
+            Some(Person {
+                first: "Jacob".into(),
+                last: "e".into()
+            }),
+            Some(Person {
+                first: "Jayden".into(),
+                last: "f".into()
+            }),
+            None,
+        ]
+    );
+    assert_eq!(
+        MaybeNamed::from_value(json!({"missing": "different"})),
+        Some(MaybeNamed { first: None })
+    );
+    assert_eq!(MaybeNamed::from_value(json!({"first": 1})), None);
+    assert_eq!(MaybeNamed::from_value(json!(null)), None);
+    assert_eq!(MaybeNamed::from_value(json!(["first"])), None);
+}
+
+#[derive(Debug, PartialEq, JMESPath)]
+struct Tagged<T> {
+    tags: Vec<T>,
+}
+
+#[test]
+fn generic() {
+    assert_eq!(
+        Tagged::<u8>::from_value(json!({"tags": [1, 2]})),
+        Some(Tagged { tags: vec![1, 2] })
+    );
+    assert_eq!(Tagged::<u8>::from_value(json!({"tags": ["1"]})), None);
+}